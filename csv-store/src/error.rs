@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Could not parse {1:?} in line {0} as a date (expected YYYY-MM-DD, optionally followed by HH:MM)")]
+    InvalidDate(u64, String),
+    #[error("Could not parse {1:?} in line {0} as a decimal number")]
+    InvalidDecimal(u64, String),
+    #[error("Could not parse {1:?} in line {0} as a unit (expected kg, g, l or lb)")]
+    InvalidUnit(u64, String),
+    #[error("Receipt {0} does not exist in the CSV file")]
+    ReceiptNotFound(String),
+}
@@ -0,0 +1,330 @@
+//! Receipts read from a CSV file, for purchases in shops that have no API.
+//!
+//! The file has a header line and one line per receipt item, with these columns:
+//!
+//! | Column       | Description                                                           |
+//! |--------------|-----------------------------------------------------------------------|
+//! | `date`       | Date of the purchase, as `YYYY-MM-DD` or `YYYY-MM-DD HH:MM`           |
+//! | `store`      | Name of the store                                                     |
+//! | `item`       | Name of the item                                                      |
+//! | `barcode`    | Barcode of the item; if empty, one is made up from the item name      |
+//! | `quantity`   | Number of units, or weight in kg for items sold by weight             |
+//! | `unit_price` | Price of each unit (or kg), without discounts                         |
+//! | `discount`   | Total discount of the line; may be empty                              |
+//! | `unit`       | Unit of the quantity (see below); optional                            |
+//!
+//! Lines with the same date and store make up a receipt.
+//!
+//! The `unit` column accepts these units, in any case and with or without a trailing dot:
+//! - pieces: `pcs`, `pc`, `st` or `stk`
+//! - kilograms: `kg`
+//! - grams: `g` or `gr`
+//! - liters: `l` or `ltr`
+//! - pounds: `lb` or `lbs`
+//!
+//! Decimal numbers may use either a dot or a comma as decimal separator, but in the
+//! latter case they must be quoted (e.g., `"0,85"`), unless the columns of the whole file
+//! are separated by semicolons instead of commas.
+//! Quantities without a unit are assumed to be weights in kg if they have a fractional
+//! part (e.g., `0,85`), and a number of units otherwise (even if written as `2.0`).
+//!
+//! ```csv
+//! date,store,item,barcode,quantity,unit_price,discount
+//! 2024-01-06 10:30,Farmers Market,Tomatoes,,"0,85",4.50,
+//! 2024-01-13,Corner Shop,Oat milk,5411188112709,2,1.99,0.40
+//! ```
+
+use std::{collections::BTreeMap, fs::File, io::Read, path::Path};
+
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime};
+use csv::{ReaderBuilder, StringRecord};
+use ir::{
+    money::parse_decimal, Currency, Discount, DiscountKind, ItemKind, Money, ReceiptDetailed,
+    ReceiptItem, ReceiptPage, ReceiptSummary, Store, StoreApi, Unit,
+};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::error::Error;
+
+pub mod error;
+
+/// Number of receipts in each page returned by [`CsvStore::get_receipts_page`]
+const RECEIPTS_PER_PAGE: usize = 20;
+
+#[derive(Debug, Deserialize)]
+struct Row {
+    date: String,
+    store: String,
+    item: String,
+    #[serde(default)]
+    barcode: String,
+    quantity: String,
+    unit_price: String,
+    #[serde(default)]
+    discount: String,
+    #[serde(default)]
+    unit: String,
+}
+
+pub struct CsvStore {
+    /// Receipts ordered from newest to oldest
+    receipts: Vec<ReceiptDetailed>,
+}
+
+impl CsvStore {
+    /// Read the receipts of a CSV file, whose prices are all in the given currency
+    pub fn open(path: impl AsRef<Path>, currency: Currency) -> Result<Self> {
+        Self::from_reader(File::open(path)?, currency)
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R, currency: Currency) -> Result<Self> {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+
+        // spreadsheets in locales that use a comma as decimal separator export
+        // CSV files separated by semicolons
+        let header = contents.lines().next().unwrap_or_default();
+        let delimiter = if header.contains(';') && !header.contains(',') {
+            b';'
+        } else {
+            b','
+        };
+
+        let mut receipts: BTreeMap<(NaiveDateTime, String), Vec<ReceiptItem>> = BTreeMap::new();
+        let mut csv_reader = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .trim(csv::Trim::All)
+            .from_reader(contents.as_bytes());
+        let headers = csv_reader.headers()?.clone();
+        let mut record = StringRecord::new();
+        while csv_reader.read_record(&mut record)? {
+            let line = record
+                .position()
+                .map(|position| position.line())
+                .unwrap_or_default();
+            let row: Row = record.deserialize(Some(&headers))?;
+            let date = parse_date(&row.date).ok_or(Error::InvalidDate(line, row.date.clone()))?;
+            let item = parse_item(&row, line, &currency)?;
+
+            receipts.entry((date, row.store)).or_default().push(item);
+        }
+
+        Ok(Self {
+            receipts: receipts
+                .into_iter()
+                .rev()
+                .map(|((date, store), items)| ReceiptDetailed {
+                    id: format!("{} {}", date.format("%Y-%m-%dT%H:%M"), store),
+                    items,
+                    date,
+                    currency: currency.clone(),
+                    store: Store {
+                        id: store.clone(),
+                        name: store,
+                        address: vec![],
+                        country: None,
+                    },
+                    taxes: vec![],
+                    total: None,
+                    total_discount: None,
+                    language: None,
+                    tenders: vec![],
+                    sources: None,
+                })
+                .collect(),
+        })
+    }
+}
+
+impl StoreApi for CsvStore {
+    fn get_receipts_page(&self, page: u32) -> Result<ReceiptPage> {
+        let start = (page.saturating_sub(1) as usize) * RECEIPTS_PER_PAGE;
+        let receipts: Vec<_> = self
+            .receipts
+            .iter()
+            .skip(start)
+            .take(RECEIPTS_PER_PAGE)
+            .map(summarize)
+            .collect::<Result<_>>()?;
+
+        Ok(ReceiptPage {
+            has_more: start + receipts.len() < self.receipts.len(),
+            receipts,
+        })
+    }
+
+    fn get_specific_receipt(&self, receipt: &ReceiptSummary) -> Result<ReceiptDetailed> {
+        Ok(self
+            .receipts
+            .iter()
+            .find(|detailed| detailed.id == receipt.id)
+            .ok_or_else(|| Error::ReceiptNotFound(receipt.id.clone()))?
+            .clone())
+    }
+}
+
+/// Symbol of some common currencies (e.g., those of the countries where Lidl has stores),
+/// to show amounts in the currency of a CSV file without asking for its symbol
+pub fn currency_symbol(id: &str) -> Option<&'static str> {
+    let symbol = match id {
+        "EUR" => "€",
+        "GBP" => "£",
+        "USD" => "$",
+        "CHF" => "CHF",
+        "SEK" | "DKK" | "NOK" => "kr",
+        "PLN" => "zł",
+        "CZK" => "Kč",
+        "HUF" => "Ft",
+        "RON" => "lei",
+        "BGN" => "лв",
+        "RSD" => "din",
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+fn summarize(receipt: &ReceiptDetailed) -> Result<ReceiptSummary> {
+    let reconciliation = receipt.reconcile()?;
+    Ok(ReceiptSummary {
+        id: receipt.id.clone(),
+        date: receipt.date,
+        total_amount: reconciliation.items_total,
+        articles_count: u32::try_from(receipt.items.len()).ok(),
+        store_id: Some(receipt.store.id.clone()),
+    })
+}
+
+fn parse_date(date: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M"))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+}
+
+fn parse_item(row: &Row, line: u64, currency: &Currency) -> Result<ReceiptItem, Error> {
+    let decimal =
+        |value: &str| parse_decimal(value).map_err(|_| Error::InvalidDecimal(line, value.into()));
+
+    let unit_price = Money::new(decimal(&row.unit_price)?, currency.clone());
+    let quantity = decimal(&row.quantity)?;
+    let discount = if row.discount.is_empty() {
+        Decimal::ZERO
+    } else {
+        decimal(&row.discount)?.abs()
+    };
+
+    let unit = if !row.unit.is_empty() {
+        Unit::from_symbol(&row.unit).ok_or_else(|| Error::InvalidUnit(line, row.unit.clone()))?
+    } else if !quantity.fract().is_zero() {
+        Unit::Kilogram
+    } else {
+        Unit::Piece
+    };
+
+    let barcode = if row.barcode.is_empty() {
+        // items without a barcode (e.g., from a market) still need a stable one,
+        // so that they can be associated with a product in Grocy
+        format!("csv-{}", row.item.to_lowercase().replace(' ', "-"))
+    } else {
+        row.barcode.clone()
+    };
+
+    Ok(ReceiptItem {
+        kind: ItemKind::from_description(&row.item, unit_price.amount.is_sign_negative()),
+        unit_price,
+        quantity,
+        unit,
+        name: row.item.clone(),
+        barcode,
+        discounts: if discount.is_zero() {
+            vec![]
+        } else {
+            vec![Discount {
+                amount: Money::new(discount, currency.clone()),
+                description: None,
+                promotion_id: None,
+                kind: DiscountKind::Other,
+            }]
+        },
+        tax: None,
+        sources: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use ir::{money::parse_decimal, test_util::eur, ItemKind, ReceiptFilter, StoreApi, Unit};
+
+    use super::CsvStore;
+
+    #[test]
+    fn test_csv_store() -> Result<()> {
+        let store = CsvStore::from_reader(include_str!("../test/receipts.csv").as_bytes(), eur())?;
+
+        let receipts: Vec<_> = store
+            .list_receipts(ReceiptFilter::default())
+            .collect::<Result<_>>()?;
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].store_id.as_deref(), Some("Corner Shop"));
+        assert_eq!(receipts[0].total_amount.amount, parse_decimal("4.08")?);
+        assert_eq!(receipts[1].store_id.as_deref(), Some("Farmers Market"));
+        assert_eq!(receipts[1].articles_count, Some(3));
+
+        let receipt = store.get_specific_receipt(&receipts[1])?;
+        assert_eq!(receipt.store.name, "Farmers Market");
+        assert_eq!(receipt.items[0].barcode, "csv-tomatoes");
+        assert_eq!(receipt.items[0].quantity, parse_decimal("0.85")?);
+        assert_eq!(receipt.items[0].unit, Unit::Kilogram);
+        assert_eq!(receipt.items[1].unit, Unit::Piece);
+        assert_eq!(
+            receipt.items[1].total_discount()?.amount,
+            parse_decimal("0.10")?
+        );
+        assert_eq!(
+            receipt.reconcile()?.items_total.amount,
+            parse_decimal("10.03")?
+        );
+
+        let receipt = store.get_specific_receipt(&receipts[0])?;
+        assert_eq!(receipt.items[0].barcode, "5411188112709");
+        assert_eq!(receipt.items[1].kind, ItemKind::Deposit);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_store_semicolons() -> Result<()> {
+        let csv = "date;store;item;barcode;quantity;unit_price;discount;unit\n\
+                   2024-01-06;Market;Apples;;1,5;2,00;;\n\
+                   2024-01-06;Market;Cider;;1;3,00;;l\n\
+                   2024-01-06;Market;Pears;;2,0;0,50;;\n";
+        let store = CsvStore::from_reader(csv.as_bytes(), eur())?;
+
+        let receipt = store.get_specific_receipt(&store.get_available_receipts()?[0])?;
+        assert_eq!(receipt.items[0].quantity, parse_decimal("1.5")?);
+        assert_eq!(receipt.items[0].unit, Unit::Kilogram);
+        assert_eq!(receipt.items[1].unit, Unit::Liter);
+        assert_eq!(receipt.items[2].unit, Unit::Piece);
+        assert_eq!(receipt.reconcile()?.items_total.amount, parse_decimal("7")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_store_invalid_line() {
+        let csv = "date,store,item,barcode,quantity,unit_price,discount\n\
+                   2024-01-06,Market,Apples,,1,2.00,\n\
+                   2024-01-06,Market,Pears,,one,2.00,\n";
+        let error = CsvStore::from_reader(csv.as_bytes(), eur())
+            .err()
+            .expect("quantity should not be parsed");
+        assert!(error.to_string().contains("line 3"));
+    }
+}
@@ -0,0 +1,31 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Grocy could not find what was asked for: {0}")]
+    NotFound(String),
+    #[error("Grocy did not accept the API key: {0}")]
+    Unauthorized(String),
+    #[error("Grocy did not accept the request: {0}")]
+    Validation(String),
+    #[error("Grocy answered with an unexpected HTTP status {0}: {1}")]
+    UnexpectedStatus(u16, String),
+    #[error("Grocy answered with an unexpected response: {0}")]
+    UnexpectedPayload(String),
+    #[error("Could not connect to Grocy: {0}")]
+    Connection(String),
+}
+
+impl Error {
+    /// Error for a response with an unsuccessful status, given the error message
+    /// that Grocy answered with
+    pub(crate) fn from_status(status: StatusCode, message: String) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Self::Unauthorized(message),
+            StatusCode::NOT_FOUND => Self::NotFound(message),
+            StatusCode::BAD_REQUEST => Self::Validation(message),
+            status => Self::UnexpectedStatus(status.as_u16(), message),
+        }
+    }
+}
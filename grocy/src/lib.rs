@@ -2,13 +2,21 @@ use std::collections::HashMap;
 
 use anyhow::Result;
 use chrono::NaiveDate;
-use reqwest::{blocking::Client, header::HeaderMap};
+use reqwest::{
+    blocking::{Client, RequestBuilder, Response},
+    header::HeaderMap,
+};
+use serde::de::DeserializeOwned;
 use structs::{
-    AddProductBarcodePayload, Location, ObjectCreated, Product, ProductDetails,
-    PurchaseProductPayload, QuantityUnit, ShoppingLocation, Transaction,
-    UpdateBarcodeLastPricePayload, UserField,
+    AddProductBarcodePayload, AddProductPayload, AddQuantityUnitConversionPayload,
+    AddShoppingLocationPayload, ErrorResponse, Location, ObjectCreated, Product, ProductBarcode,
+    ProductDetails, ProductGroup, PurchaseProductPayload, QuantityUnit, QuantityUnitConversion,
+    ShoppingLocation, StockEntry, Transaction, UpdateBarcodeLastPricePayload, UserField,
 };
 
+use crate::error::Error;
+
+pub mod error;
 pub mod structs;
 
 const GROCY_TOKEN_HEADER: &str = "GROCY-API-KEY";
@@ -31,55 +39,173 @@ impl GrocyApi {
     }
 
     pub fn get_shopping_locations(&self) -> Result<Vec<ShoppingLocation>> {
-        Ok(self
-            .client
-            .get(format!("{}/api/objects/shopping_locations", self.base_url))
-            .query(&[("query[]", "active=1")])
-            .send()?
-            .json()?)
+        Ok(parse_json(send(
+            self.client
+                .get(format!("{}/api/objects/shopping_locations", self.base_url))
+                .query(&[("query[]", "active=1")]),
+        )?)?)
     }
 
+    pub fn create_shopping_location(
+        &self,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<ObjectCreated> {
+        Ok(parse_json(send(
+            self.client
+                .post(format!("{}/api/objects/shopping_locations", self.base_url))
+                .json(&AddShoppingLocationPayload { name, description }),
+        )?)?)
+    }
+
+    /// Product that a barcode belongs to, failing with [`Error::NotFound`] if the barcode
+    /// is not known to Grocy
     pub fn get_product_by_barcode(&self, barcode: &str) -> Result<ProductDetails> {
-        Ok(self
-            .client
-            .get(format!(
-                "{}/api/stock/products/by-barcode/{}",
-                self.base_url, barcode
-            ))
-            .send()?
-            .json()?)
+        let response = send(self.client.get(format!(
+            "{}/api/stock/products/by-barcode/{}",
+            self.base_url, barcode
+        )));
+        match response {
+            // Grocy answers unknown barcodes as a bad request, instead of not found
+            Err(Error::Validation(message)) if message.starts_with("No product with barcode") => {
+                Err(Error::NotFound(message).into())
+            }
+            response => Ok(parse_json(response?)?),
+        }
     }
 
     pub fn get_all_products(&self) -> Result<Vec<Product>> {
-        Ok(self
-            .client
-            .get(format!("{}/api/objects/products", self.base_url))
-            .query(&[("query[]", "active=1")])
-            .send()?
-            .json()?)
+        Ok(parse_json(send(
+            self.client
+                .get(format!("{}/api/objects/products", self.base_url))
+                .query(&[("query[]", "active=1")]),
+        )?)?)
+    }
+
+    pub fn get_product_groups(&self) -> Result<Vec<ProductGroup>> {
+        Ok(parse_json(send(
+            self.client
+                .get(format!("{}/api/objects/product_groups", self.base_url))
+                .query(&[("query[]", "active=1")]),
+        )?)?)
+    }
+
+    /// Create a product whose stock, consume and price quantity units are all
+    /// `qu_id_stock`; if `qu_id_purchase` is another unit, a conversion between them
+    /// must be created for the product
+    pub fn create_product(
+        &self,
+        name: &str,
+        product_group_id: Option<u32>,
+        location_id: u32,
+        qu_id_stock: u32,
+        qu_id_purchase: u32,
+        default_best_before_days: i32,
+    ) -> Result<ObjectCreated> {
+        Ok(parse_json(send(
+            self.client
+                .post(format!("{}/api/objects/products", self.base_url))
+                .json(&AddProductPayload {
+                    name,
+                    product_group_id,
+                    location_id,
+                    qu_id_stock,
+                    qu_id_purchase,
+                    qu_id_consume: qu_id_stock,
+                    qu_id_price: qu_id_stock,
+                    default_best_before_days,
+                }),
+        )?)?)
+    }
+
+    pub fn delete_product(&self, product_id: u32) -> Result<()> {
+        send(self.client.delete(format!(
+            "{}/api/objects/products/{}",
+            self.base_url, product_id
+        )))?;
+
+        Ok(())
+    }
+
+    /// Entries of a product that are currently in stock
+    pub fn get_product_stock_entries(&self, product_id: u32) -> Result<Vec<StockEntry>> {
+        Ok(parse_json(send(self.client.get(format!(
+            "{}/api/stock/products/{}/entries",
+            self.base_url, product_id
+        )))?)?)
+    }
+
+    pub fn get_product_barcodes(&self, product_id: u32) -> Result<Vec<ProductBarcode>> {
+        Ok(parse_json(send(
+            self.client
+                .get(format!("{}/api/objects/product_barcodes", self.base_url))
+                .query(&[("query[]", format!("product_id={}", product_id))]),
+        )?)?)
+    }
+
+    /// Create a conversion for a single product, where one `from_qu_id` is `factor`
+    /// of `to_qu_id`
+    pub fn create_quantity_unit_conversion(
+        &self,
+        product_id: u32,
+        from_qu_id: u32,
+        to_qu_id: u32,
+        factor: f64,
+    ) -> Result<ObjectCreated> {
+        Ok(parse_json(send(
+            self.client
+                .post(format!(
+                    "{}/api/objects/quantity_unit_conversions",
+                    self.base_url
+                ))
+                .json(&AddQuantityUnitConversionPayload {
+                    product_id,
+                    from_qu_id,
+                    to_qu_id,
+                    factor,
+                }),
+        )?)?)
     }
 
     pub fn get_quantity_unit(&self, id: u32) -> Result<QuantityUnit> {
-        Ok(self
-            .client
-            .get(format!(
-                "{}/api/objects/quantity_units/{}",
-                self.base_url, id
-            ))
-            .send()?
-            .json()?)
+        Ok(parse_json(send(self.client.get(format!(
+            "{}/api/objects/quantity_units/{}",
+            self.base_url, id
+        )))?)?)
+    }
+
+    pub fn get_quantity_units(&self) -> Result<Vec<QuantityUnit>> {
+        Ok(parse_json(send(self.client.get(format!(
+            "{}/api/objects/quantity_units",
+            self.base_url
+        )))?)?)
+    }
+
+    /// Conversions between quantity units that apply to a product, including the
+    /// ones that Grocy derives from other conversions
+    pub fn get_quantity_unit_conversions(
+        &self,
+        product_id: u32,
+    ) -> Result<Vec<QuantityUnitConversion>> {
+        Ok(parse_json(send(
+            self.client
+                .get(format!(
+                    "{}/api/objects/quantity_unit_conversions_resolved",
+                    self.base_url
+                ))
+                .query(&[("query[]", format!("product_id={}", product_id))]),
+        )?)?)
     }
 
     pub fn get_barcode_userfields(&self) -> Result<Vec<UserField>> {
         // can be generalized for more than barcodes in the future if necessary;
         // not using an arg for now to provide stronger typing guarantees than
         // if the function accepted an arbitrary entity_name string that might fail
-        Ok(self
-            .client
-            .get(format!("{}/api/objects/userfields", self.base_url))
-            .query(&[("query[]", "entity=product_barcodes")])
-            .send()?
-            .json()?)
+        Ok(parse_json(send(
+            self.client
+                .get(format!("{}/api/objects/userfields", self.base_url))
+                .query(&[("query[]", "entity=product_barcodes")]),
+        )?)?)
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -93,54 +219,78 @@ impl GrocyApi {
         note: Option<&str>,
         userfields: HashMap<String, String>,
     ) -> Result<ObjectCreated> {
-        let barcode: ObjectCreated = self
-            .client
-            .post(format!("{}/api/objects/product_barcodes", self.base_url))
-            .json(&AddProductBarcodePayload {
-                product_id,
-                barcode,
-                amount: quantity,
-                qu_id: quantity_unit_id,
-                shopping_location_id,
-                note,
-            })
-            .send()?
-            .json()?;
+        let barcode: ObjectCreated = parse_json(send(
+            self.client
+                .post(format!("{}/api/objects/product_barcodes", self.base_url))
+                .json(&AddProductBarcodePayload {
+                    product_id,
+                    barcode,
+                    amount: quantity,
+                    qu_id: quantity_unit_id,
+                    shopping_location_id,
+                    note,
+                }),
+        )?)?;
 
         if !userfields.is_empty() {
-            self.client
-                .put(format!(
-                    "{}/api/userfields/product_barcodes/{}",
-                    self.base_url, barcode.created_object_id
-                ))
-                .json(&userfields)
-                .send()?;
+            send(
+                self.client
+                    .put(format!(
+                        "{}/api/userfields/product_barcodes/{}",
+                        self.base_url, barcode.created_object_id
+                    ))
+                    .json(&userfields),
+            )?;
         }
 
         Ok(barcode)
     }
 
-    pub fn update_barcode_last_price(&self, barcode_object_id: u32, last_price: f64) -> Result<()> {
+    pub fn update_barcode_last_price(
+        &self,
+        barcode_object_id: u32,
+        last_price: Option<f64>,
+    ) -> Result<()> {
         // Grocy uses PATCH semantics for PUT so we only need to pass last_price
         // see: https://github.com/grocy/grocy/blob/6602c76005bfdb436681d976e130407650719a4a/public/viewjs/purchase.js#L83
-        self.client
-            .put(format!(
-                "{}/api/objects/product_barcodes/{}",
-                self.base_url, barcode_object_id
-            ))
-            .json(&UpdateBarcodeLastPricePayload { last_price })
-            .send()?;
+        send(
+            self.client
+                .put(format!(
+                    "{}/api/objects/product_barcodes/{}",
+                    self.base_url, barcode_object_id
+                ))
+                .json(&UpdateBarcodeLastPricePayload { last_price }),
+        )?;
+
+        Ok(())
+    }
+
+    pub fn delete_product_barcode(&self, barcode_object_id: u32) -> Result<()> {
+        send(self.client.delete(format!(
+            "{}/api/objects/product_barcodes/{}",
+            self.base_url, barcode_object_id
+        )))?;
+
+        Ok(())
+    }
+
+    /// Undo a stock transaction (e.g., a purchase), given the `transaction_id`
+    /// of its [`Transaction`]s
+    pub fn undo_transaction(&self, transaction_id: &str) -> Result<()> {
+        send(self.client.post(format!(
+            "{}/api/stock/transactions/{}/undo",
+            self.base_url, transaction_id
+        )))?;
 
         Ok(())
     }
 
     pub fn get_locations(&self) -> Result<Vec<Location>> {
-        Ok(self
-            .client
-            .get(format!("{}/api/objects/locations", self.base_url))
-            .query(&[("query[]", "active=1")])
-            .send()?
-            .json()?)
+        Ok(parse_json(send(
+            self.client
+                .get(format!("{}/api/objects/locations", self.base_url))
+                .query(&[("query[]", "active=1")]),
+        )?)?)
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -155,26 +305,52 @@ impl GrocyApi {
         shopping_location: Option<u32>,
         note: Option<&str>,
     ) -> Result<Vec<Transaction>> {
-        Ok(self
-            .client
-            .post(format!(
-                "{}/api/stock/products/{}/add",
-                self.base_url, product
-            ))
-            .json(&PurchaseProductPayload {
-                amount,
-                best_before_date: best_before_date
-                    .map_or("2999-12-31".to_string(), |date| date.to_string())
-                    .as_ref(),
-                purchased_date: purchased_date.map(|date| date.to_string()).as_deref(),
-                transaction_type: "purchase",
-                price,
-                location_id: location,
-                shopping_location_id: shopping_location,
-                stock_label_type: 0,
-                note,
-            })
-            .send()?
-            .json()?)
+        Ok(parse_json(send(
+            self.client
+                .post(format!(
+                    "{}/api/stock/products/{}/add",
+                    self.base_url, product
+                ))
+                .json(&PurchaseProductPayload {
+                    amount,
+                    best_before_date: best_before_date
+                        .map_or("2999-12-31".to_string(), |date| date.to_string())
+                        .as_ref(),
+                    purchased_date: purchased_date.map(|date| date.to_string()).as_deref(),
+                    transaction_type: "purchase",
+                    price,
+                    location_id: location,
+                    shopping_location_id: shopping_location,
+                    stock_label_type: 0,
+                    note,
+                }),
+        )?)?)
+    }
+}
+
+/// Send a request, and turn unsuccessful responses into errors with the message
+/// that Grocy answered with
+fn send(request: RequestBuilder) -> Result<Response, Error> {
+    let response = request
+        .send()
+        .map_err(|error| Error::Connection(error.to_string()))?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
     }
+
+    let message = match response.json::<ErrorResponse>() {
+        Ok(error) => error.error_message,
+        Err(_) => status
+            .canonical_reason()
+            .unwrap_or("no error message")
+            .to_string(),
+    };
+    Err(Error::from_status(status, message))
+}
+
+fn parse_json<T: DeserializeOwned>(response: Response) -> Result<T, Error> {
+    response
+        .json()
+        .map_err(|error| Error::UnexpectedPayload(error.to_string()))
 }
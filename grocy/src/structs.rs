@@ -50,6 +50,8 @@ pub struct ProductBarcode {
     pub qu_id: Option<u32>,
     pub amount: Option<f64>,
     pub note: Option<String>,
+    #[serde(default)]
+    pub last_price: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,6 +61,32 @@ pub struct QuantityUnit {
     pub name_plural: String,
 }
 
+impl Display for QuantityUnit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProductGroup {
+    pub id: u32,
+    pub name: String,
+}
+
+impl Display for ProductGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.name.fmt(f)
+    }
+}
+
+/// Conversion between two quantity units, either for all products or for a single one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuantityUnitConversion {
+    pub from_qu_id: u32,
+    pub to_qu_id: u32,
+    pub factor: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserField {
     pub id: u32,
@@ -80,6 +108,32 @@ pub struct AddProductBarcodePayload<'a> {
     pub note: Option<&'a str>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddShoppingLocationPayload<'a> {
+    pub name: &'a str,
+    pub description: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddProductPayload<'a> {
+    pub name: &'a str,
+    pub product_group_id: Option<u32>,
+    pub location_id: u32,
+    pub qu_id_stock: u32,
+    pub qu_id_purchase: u32,
+    pub qu_id_consume: u32,
+    pub qu_id_price: u32,
+    pub default_best_before_days: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddQuantityUnitConversionPayload {
+    pub product_id: u32,
+    pub from_qu_id: u32,
+    pub to_qu_id: u32,
+    pub factor: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ObjectCreated {
     pub created_object_id: String,
@@ -87,7 +141,7 @@ pub struct ObjectCreated {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateBarcodeLastPricePayload {
-    pub last_price: f64,
+    pub last_price: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -123,6 +177,22 @@ pub struct PurchaseProductPayload<'a> {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub id: u32,
+    /// Id shared by all the stock entries of a purchase, which is used to undo it
+    pub transaction_id: String,
+}
+
+/// Amount of a product in stock from a single purchase
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockEntry {
+    pub id: u32,
+    pub product_id: u32,
+    pub amount: f64,
+}
+
+/// Body of the responses of Grocy to requests that failed
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ErrorResponse {
+    pub error_message: String,
 }
 
 // e.g., for when deserializing -1 into an Option<u32>
@@ -0,0 +1,161 @@
+//! Tests of how the Grocy API client handles unsuccessful responses, against a local
+//! server that answers like Grocy does.
+
+use std::thread;
+
+use grocy::{error::Error, GrocyApi};
+use tiny_http::{Header, Method, Response, Server};
+
+const API_KEY: &str = "test-api-key";
+
+/// Start a server in the background that answers like Grocy, and get its base URL
+fn start_server() -> String {
+    let server = Server::http("127.0.0.1:0").expect("server should start");
+    let address = server
+        .server_addr()
+        .to_ip()
+        .expect("server should listen on an ip address");
+
+    thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let is_authorized = request.headers().iter().any(|header| {
+                header.field.equiv("GROCY-API-KEY") && header.value.as_str() == API_KEY
+            });
+            let path = request
+                .url()
+                .split('?')
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            let mut body = String::new();
+            let _ = request.as_reader().read_to_string(&mut body);
+
+            let (status, response) = match (request.method(), path.as_str()) {
+                _ if !is_authorized => (401, r#"{ "error_message": "Unauthorized" }"#),
+                (Method::Get, "/api/stock/products/by-barcode/0000000") => (
+                    400,
+                    r#"{ "error_message": "No product with barcode 0000000 found" }"#,
+                ),
+                (Method::Get, "/api/objects/locations") => (200, r#"[{ "id": 1 }]"#),
+                (Method::Post, "/api/stock/transactions/x6543210/undo") => (204, ""),
+                (Method::Post, "/api/stock/transactions/x0123456/undo") => (
+                    400,
+                    r#"{ "error_message": "This booking cannot be undone" }"#,
+                ),
+                (Method::Delete, "/api/objects/product_barcodes/7") => (204, ""),
+                (Method::Get, "/api/stock/products/12/entries") => {
+                    (200, r#"[{ "id": 3, "product_id": 12, "amount": 2.5 }]"#)
+                }
+                (Method::Get, "/api/objects/product_barcodes") => (
+                    200,
+                    r#"[{ "id": 7, "product_id": 12, "barcode": "20012345", "qu_id": null, "amount": null, "note": null }]"#,
+                ),
+                (Method::Post, "/api/objects/products") if body.contains(r#""qu_id_price":3"#) => {
+                    (200, r#"{ "created_object_id": "12" }"#)
+                }
+                (Method::Post, "/api/objects/products") => {
+                    (400, r#"{ "error_message": "Quantity units are invalid" }"#)
+                }
+                (Method::Post, "/api/stock/products/1/add") => {
+                    (400, r#"{ "error_message": "Provided amount is invalid" }"#)
+                }
+                _ => (404, ""),
+            };
+
+            let content_type = Header::from_bytes("Content-Type", "application/json")
+                .expect("header should be valid");
+            let _ = request.respond(
+                Response::from_string(response)
+                    .with_status_code(status)
+                    .with_header(content_type),
+            );
+        }
+    });
+
+    format!("http://{}", address)
+}
+
+#[test]
+fn test_unknown_barcode() {
+    let api = GrocyApi::new(&start_server(), API_KEY).expect("client should be built");
+
+    let error = api
+        .get_product_by_barcode("0000000")
+        .expect_err("barcode should be unknown");
+    assert!(matches!(error.downcast_ref(), Some(Error::NotFound(_))));
+}
+
+#[test]
+fn test_undo_import() {
+    let api = GrocyApi::new(&start_server(), API_KEY).expect("client should be built");
+
+    api.undo_transaction("x6543210")
+        .expect("transaction should be undone");
+    api.delete_product_barcode(7)
+        .expect("barcode should be deleted");
+
+    // created products are only deleted if nothing else refers to them
+    let entries = api
+        .get_product_stock_entries(12)
+        .expect("stock entries should be fetched");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].amount, 2.5);
+    let barcodes = api
+        .get_product_barcodes(12)
+        .expect("barcodes should be fetched");
+    assert_eq!(barcodes[0].id, 7);
+
+    let error = api
+        .undo_transaction("x0123456")
+        .expect_err("transaction should not be undone");
+    assert!(matches!(
+        error.downcast_ref(),
+        Some(Error::Validation(message)) if message == "This booking cannot be undone"
+    ));
+}
+
+#[test]
+fn test_create_product() {
+    let api = GrocyApi::new(&start_server(), API_KEY).expect("client should be built");
+
+    // prices are sent per stock unit, so the price unit must be the stock unit
+    let product = api
+        .create_product("Grytbitar", None, 1, 3, 2, 5)
+        .expect("product should be created");
+    assert_eq!(product.created_object_id, "12");
+}
+
+#[test]
+fn test_unsuccessful_responses() {
+    let base_url = start_server();
+
+    let api = GrocyApi::new(&base_url, "wrong-api-key").expect("client should be built");
+    let error = api
+        .get_locations()
+        .expect_err("API key should not be accepted");
+    assert!(matches!(error.downcast_ref(), Some(Error::Unauthorized(_))));
+
+    let api = GrocyApi::new(&base_url, API_KEY).expect("client should be built");
+    let error = api
+        .purchase_product(1, -1.0, None, None, None, None, None, None)
+        .expect_err("purchase should not be accepted");
+    assert!(matches!(
+        error.downcast_ref(),
+        Some(Error::Validation(message)) if message == "Provided amount is invalid"
+    ));
+
+    // a response that is not what was expected is not mistaken for a missing object
+    let error = api
+        .get_locations()
+        .expect_err("locations should not be parsed");
+    assert!(matches!(
+        error.downcast_ref(),
+        Some(Error::UnexpectedPayload(_))
+    ));
+
+    let error = GrocyApi::new("http://127.0.0.1:1", API_KEY)
+        .expect("client should be built")
+        .get_locations()
+        .expect_err("Grocy should not be reachable");
+    assert!(matches!(error.downcast_ref(), Some(Error::Connection(_))));
+}
@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Receipt file has version {0}, but only version {1} is supported")]
+    UnsupportedFileVersion(u64, u32),
+    #[error("Receipt file does not have a version field")]
+    MissingFileVersion,
+    #[error("Could not parse {0:?} as a decimal number")]
+    InvalidDecimal(String),
+    #[error("Cannot do arithmetic between amounts in {0} and in {1}")]
+    CurrencyMismatch(String, String),
+}
@@ -0,0 +1,196 @@
+//! Receipt file format, used to save receipts to disk and load them back.
+//!
+//! A receipt file is a JSON document with a `version` field and the receipt itself,
+//! serialized as-is from [`ReceiptDetailed`]:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "receipt": {
+//!     "id": "1234567890",
+//!     "items": [
+//!       {
+//!         "unit_price": { "amount": "79.90", "currency": { "id": "SEK", "symbol": "kr" } },
+//!         "quantity": "2",
+//!         "unit": "piece",
+//!         "name": "Grytbitar",
+//!         "barcode": "lidl-0051496",
+//!         "discounts": [
+//!           {
+//!             "amount": { "amount": "7.92", "currency": { "id": "SEK", "symbol": "kr" } },
+//!             "description": "Lidl Plus-rabatt",
+//!             "promotion_id": "100001234",
+//!             "kind": "loyalty"
+//!           }
+//!         ],
+//!         "tax": { "category": "C", "rate": "12" },
+//!         "kind": "product"
+//!       }
+//!     ],
+//!     "date": "2024-01-01T12:00:00",
+//!     "currency": { "id": "SEK", "symbol": "kr" },
+//!     "store": {
+//!       "id": "STORE123",
+//!       "name": "Example Store",
+//!       "address": ["Storgatan 1", "123 45 Stockholm"],
+//!       "country": "SE"
+//!     },
+//!     "taxes": [
+//!       {
+//!         "category": "C",
+//!         "rate": "12",
+//!         "base_amount": { "amount": "151.88", "currency": { "id": "SEK", "symbol": "kr" } },
+//!         "tax_amount": { "amount": "16.27", "currency": { "id": "SEK", "symbol": "kr" } }
+//!       }
+//!     ],
+//!     "total": { "amount": "151.88", "currency": { "id": "SEK", "symbol": "kr" } },
+//!     "total_discount": { "amount": "7.92", "currency": { "id": "SEK", "symbol": "kr" } },
+//!     "language": "sv",
+//!     "tenders": [
+//!       {
+//!         "method": "Kort",
+//!         "amount": { "amount": "151.88", "currency": { "id": "SEK", "symbol": "kr" } }
+//!       }
+//!     ]
+//!   }
+//! }
+//! ```
+//!
+//! Amounts of money and quantities are exact decimal numbers, written as strings.
+//! Fields added after a version was released are optional, so that older files of the
+//! same version can still be read.
+//!
+//! The version is bumped whenever a change to the format would prevent older
+//! files from being read correctly.
+
+use std::io::{Read, Write};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{error::Error, ReceiptDetailed};
+
+/// Version of the receipt file format written by this crate
+pub const RECEIPT_FILE_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct ReceiptFileRef<'a> {
+    version: u32,
+    receipt: &'a ReceiptDetailed,
+}
+
+#[derive(Deserialize)]
+struct ReceiptFile {
+    receipt: ReceiptDetailed,
+}
+
+pub fn write_receipt<W: Write>(writer: W, receipt: &ReceiptDetailed) -> Result<()> {
+    serde_json::to_writer_pretty(
+        writer,
+        &ReceiptFileRef {
+            version: RECEIPT_FILE_VERSION,
+            receipt,
+        },
+    )?;
+
+    Ok(())
+}
+
+pub fn read_receipt<R: Read>(reader: R) -> Result<ReceiptDetailed> {
+    let value: Value = serde_json::from_reader(reader)?;
+
+    // check the version before deserializing the receipt, so that the user gets a
+    // meaningful error instead of a missing/unknown field one
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or(Error::MissingFileVersion)?;
+    if version != u64::from(RECEIPT_FILE_VERSION) {
+        return Err(Error::UnsupportedFileVersion(version, RECEIPT_FILE_VERSION).into());
+    }
+
+    let file: ReceiptFile = serde_json::from_value(value)?;
+    Ok(file.receipt)
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use chrono::NaiveDate;
+    use rust_decimal::Decimal;
+
+    use crate::{
+        Currency, Discount, DiscountKind, ItemKind, Money, ReceiptDetailed, ReceiptItem, Store,
+        Tax, TaxSummary, Tender, Unit,
+    };
+
+    use super::{read_receipt, write_receipt};
+
+    #[test]
+    fn test_receipt_file_roundtrip() -> Result<()> {
+        let currency = Currency {
+            id: "SEK".to_owned(),
+            symbol: "kr".to_owned(),
+        };
+        let receipt = ReceiptDetailed {
+            id: "test-id".to_owned(),
+            items: vec![ReceiptItem {
+                unit_price: Money::new(Decimal::new(7990, 2), currency.clone()),
+                quantity: Decimal::new(2, 0),
+                unit: Unit::Piece,
+                name: "Grytbitar".to_owned(),
+                barcode: "lidl-0051496".to_owned(),
+                discounts: vec![Discount {
+                    amount: Money::new(Decimal::new(792, 2), currency.clone()),
+                    description: Some("Lidl Plus-rabatt".to_owned()),
+                    promotion_id: Some("100001234".to_owned()),
+                    kind: DiscountKind::Loyalty,
+                }],
+                tax: Some(Tax {
+                    category: "C".to_owned(),
+                    rate: Some(Decimal::new(12, 0)),
+                }),
+                kind: ItemKind::Product,
+                sources: None,
+            }],
+            date: NaiveDate::from_ymd_opt(2024, 1, 1)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap(),
+            currency: currency.clone(),
+            store: Store {
+                id: "STORE123".to_owned(),
+                name: "Example Store".to_owned(),
+                address: vec![],
+                country: None,
+            },
+            taxes: vec![TaxSummary {
+                category: "C".to_owned(),
+                rate: Decimal::new(12, 0),
+                base_amount: Money::new(Decimal::new(15188, 2), currency.clone()),
+                tax_amount: Money::new(Decimal::new(1627, 2), currency.clone()),
+            }],
+            total: Some(Money::new(Decimal::new(15188, 2), currency.clone())),
+            total_discount: Some(Money::new(Decimal::new(792, 2), currency.clone())),
+            language: None,
+            tenders: vec![Tender {
+                method: "Kort".to_owned(),
+                amount: Money::new(Decimal::new(15188, 2), currency.clone()),
+            }],
+            sources: None,
+        };
+
+        let mut buffer = vec![];
+        write_receipt(&mut buffer, &receipt)?;
+        assert_eq!(receipt, read_receipt(buffer.as_slice())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_receipt_file_unsupported_version() {
+        let file = r#"{ "version": 999, "receipt": {} }"#;
+        assert!(read_receipt(file.as_bytes()).is_err());
+    }
+}
@@ -2,13 +2,37 @@ use std::fmt::Display;
 
 use anyhow::Result;
 use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+pub use listing::{ReceiptFilter, ReceiptPage, Receipts};
+pub use money::Money;
+
+pub mod error;
+pub mod file;
+mod listing;
+pub mod money;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
 pub trait StoreApi {
-    fn get_available_receipts(&self) -> Result<Vec<ReceiptSummary>>;
+    /// Get a page of the available receipts, ordered from newest to oldest.
+    /// Pages are numbered starting at 1.
+    fn get_receipts_page(&self, page: u32) -> Result<ReceiptPage>;
     fn get_specific_receipt(&self, receipt: &ReceiptSummary) -> Result<ReceiptDetailed>;
+
+    /// Get the most recent receipts (i.e., the first page)
+    fn get_available_receipts(&self) -> Result<Vec<ReceiptSummary>> {
+        Ok(self.get_receipts_page(1)?.receipts)
+    }
+
+    /// Iterate over all the receipts matching the filter, fetching pages as needed
+    fn list_receipts(&self, filter: ReceiptFilter) -> Receipts<'_, Self> {
+        Receipts::new(self, filter)
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Currency {
     /// ISO 4217 code of currency
     pub id: String,
@@ -17,13 +41,14 @@ pub struct Currency {
 }
 
 /// Used when listing available receipts; has minimal information
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ReceiptSummary {
     pub id: String,
     pub date: NaiveDateTime,
-    pub currency: Currency,
-    pub total_amount: f64,
+    pub total_amount: Money,
     pub articles_count: Option<u32>,
+    #[serde(default)]
+    pub store_id: Option<String>,
 }
 
 impl Display for ReceiptSummary {
@@ -32,38 +57,646 @@ impl Display for ReceiptSummary {
         if let Some(articles_count) = self.articles_count {
             write!(f, " - {} product(s)", articles_count)?;
         }
-        write!(f, " - {} {}", self.total_amount, self.currency.symbol)
+        write!(f, " - {}", self.total_amount)
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Store {
     pub id: String,
     pub name: String,
+    /// Address lines of the store, as printed in the header of the receipt
+    #[serde(default)]
+    pub address: Vec<String>,
+    /// Country of the store (e.g., "SE")
+    #[serde(default)]
+    pub country: Option<String>,
 }
 
-#[derive(Debug, PartialEq)]
+impl Display for Store {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.address.is_empty() {
+            write!(f, " ({})", self.address.join(", "))?;
+        }
+        if let Some(country) = &self.country {
+            write!(f, " - {}", country)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reason why a discount was given
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscountKind {
+    /// Discount for being a member of a loyalty program (e.g., "Lidl Plus-rabatt")
+    Loyalty,
+    /// Discount from a promotion available to every customer
+    Promotion,
+    /// Discount from a coupon activated or presented by the customer
+    Coupon,
+    /// Price reduction of a specific item (e.g., close to its due date)
+    Markdown,
+    #[default]
+    Other,
+}
+
+impl DiscountKind {
+    /// Guess the kind of a discount from its description in the receipt.
+    /// Descriptions are in the language of the receipt, so this only knows about some
+    /// common keywords and falls back to [`DiscountKind::Other`].
+    pub fn from_description(description: &str) -> Self {
+        const COUPON: &[&str] = &["coupon", "kupong", "kupon", "gutschein", "voucher", "cupon"];
+        const MARKDOWN: &[&str] = &["reduziert", "reduced", "nedsatt", "markdown", "abschrift"];
+        const PROMOTION: &[&str] = &[
+            "erbjud",
+            "angebot",
+            "offer",
+            "offre",
+            "oferta",
+            "aktion",
+            "promo",
+            "kampanj",
+            "preisvorteil",
+        ];
+
+        let description = description.to_lowercase();
+        let contains_any =
+            |keywords: &[&str]| keywords.iter().any(|keyword| description.contains(keyword));
+
+        if contains_any(COUPON) {
+            Self::Coupon
+        } else if description.contains("lidl plus") {
+            // offers in loyalty apps have to be activated beforehand, just like coupons
+            if contains_any(PROMOTION) {
+                Self::Coupon
+            } else {
+                Self::Loyalty
+            }
+        } else if contains_any(MARKDOWN) {
+            Self::Markdown
+        } else if contains_any(PROMOTION) {
+            Self::Promotion
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl Display for DiscountKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Loyalty => write!(f, "loyalty"),
+            Self::Promotion => write!(f, "promotion"),
+            Self::Coupon => write!(f, "coupon"),
+            Self::Markdown => write!(f, "markdown"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Discount {
-    pub amount: f64,
+    /// Amount subtracted from the price of the item, as a positive value
+    pub amount: Money,
+    /// Description of the discount as printed in the receipt
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Identifier of the promotion that gave this discount, if known
+    #[serde(default)]
+    pub promotion_id: Option<String>,
+    #[serde(default)]
+    pub kind: DiscountKind,
 }
 
-#[derive(Debug, PartialEq)]
+/// Tax applied to a receipt line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tax {
+    /// Tax category as printed in the receipt (e.g., "A")
+    pub category: String,
+    /// Tax rate as a percentage (e.g., 12 for 12%), if known
+    pub rate: Option<Decimal>,
+}
+
+/// Line of the tax summary table of a receipt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaxSummary {
+    pub category: String,
+    /// Tax rate as a percentage (e.g., 12 for 12%)
+    pub rate: Decimal,
+    /// Total amount of the lines in this category, tax included
+    pub base_amount: Money,
+    pub tax_amount: Money,
+}
+
+/// Payment made for a receipt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tender {
+    /// Payment method as printed in the receipt (e.g., "Kreditkarte")
+    pub method: String,
+    pub amount: Money,
+}
+
+/// What a receipt line is for; only products are stocked
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    #[default]
+    Product,
+    /// Deposit paid for the container of the product before it (e.g., "Pfand")
+    Deposit,
+    /// Refund for returned containers (e.g., "Leergut"), which has a negative price
+    DepositReturn,
+    /// Shopping bag bought at the till
+    Bag,
+    /// Anything else that is paid for but is not a product (e.g., a fee)
+    Service,
+}
+
+impl ItemKind {
+    /// Guess the kind of a receipt line from its description and whether its price is
+    /// negative. Like [`DiscountKind::from_description`], this only knows about some
+    /// common keywords and falls back to [`ItemKind::Product`].
+    /// Keywords must match whole words, since they are also part of many product names
+    /// (e.g., "Tütensuppe" is not a bag).
+    pub fn from_description(description: &str, is_negative: bool) -> Self {
+        const DEPOSIT: &[&str] = &["pfand", "pant", "deposit", "statiegeld", "consigne"];
+        const DEPOSIT_RETURN: &[&str] = &["leergut", "returpant", "pantretur", "empties"];
+        const BAG: &[&str] = &[
+            "tragetasche",
+            "tüte",
+            "bag",
+            "saco",
+            "bärkasse",
+            "plastkasse",
+            "papperskasse",
+        ];
+        const SERVICE: &[&str] = &["gebühr", "avgift", "service", "fee"];
+
+        let description = description.to_lowercase();
+        let words: Vec<_> = description
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        let has_any = |keywords: &[&str]| words.iter().any(|word| keywords.contains(word));
+
+        if has_any(DEPOSIT_RETURN) {
+            Self::DepositReturn
+        } else if has_any(DEPOSIT) {
+            if is_negative {
+                Self::DepositReturn
+            } else {
+                Self::Deposit
+            }
+        } else if has_any(BAG) {
+            Self::Bag
+        } else if has_any(SERVICE) {
+            Self::Service
+        } else {
+            Self::Product
+        }
+    }
+}
+
+impl Display for ItemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Product => write!(f, "product"),
+            Self::Deposit => write!(f, "deposit"),
+            Self::DepositReturn => write!(f, "deposit return"),
+            Self::Bag => write!(f, "bag"),
+            Self::Service => write!(f, "service"),
+        }
+    }
+}
+
+/// Unit of the quantity of a receipt line
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Unit {
+    /// Quantity is a number of items
+    #[default]
+    #[serde(rename = "piece")]
+    Piece,
+    #[serde(rename = "kg")]
+    Kilogram,
+    #[serde(rename = "g")]
+    Gram,
+    #[serde(rename = "l")]
+    Liter,
+    #[serde(rename = "lb")]
+    Pound,
+}
+
+/// What a unit measures; only units of the same dimension can be converted
+#[derive(PartialEq)]
+enum Dimension {
+    Count,
+    Mass,
+    Volume,
+}
+
+impl Unit {
+    pub const ALL: [Self; 5] = [
+        Self::Piece,
+        Self::Kilogram,
+        Self::Gram,
+        Self::Liter,
+        Self::Pound,
+    ];
+
+    /// Parse a unit as printed in a receipt (e.g., "kg" in "0,957 kg x 67,90 SEK/kg")
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol.to_lowercase().trim_end_matches('.') {
+            "pcs" | "pc" | "st" | "stk" => Some(Self::Piece),
+            "kg" => Some(Self::Kilogram),
+            "g" | "gr" => Some(Self::Gram),
+            "l" | "ltr" => Some(Self::Liter),
+            "lb" | "lbs" => Some(Self::Pound),
+            _ => None,
+        }
+    }
+
+    /// Names this unit might be given elsewhere (e.g., a quantity unit in Grocy),
+    /// in lowercase
+    pub fn names(self) -> &'static [&'static str] {
+        match self {
+            Self::Piece => &["piece", "pieces", "pcs", "pc"],
+            Self::Kilogram => &["kg", "kilogram", "kilograms", "kilo", "kilos"],
+            Self::Gram => &["g", "gram", "grams", "gramm", "gramas"],
+            Self::Liter => &["l", "liter", "liters", "litre", "litres", "litro", "litros"],
+            Self::Pound => &["lb", "lbs", "pound", "pounds"],
+        }
+    }
+
+    /// Find the unit with the given name (e.g., "Kilogram"), ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|unit| unit.names().contains(&name.as_str()))
+    }
+
+    /// Whether the quantity is measured (e.g., weighed at the till) instead of counted
+    pub fn is_measured(self) -> bool {
+        self != Self::Piece
+    }
+
+    /// Factor to multiply a quantity in this unit by to get it in `other`,
+    /// if both units measure the same thing (e.g., 1000 from kilograms to grams)
+    pub fn factor_to(self, other: Self) -> Option<Decimal> {
+        let (dimension, factor) = self.in_base_unit();
+        let (other_dimension, other_factor) = other.in_base_unit();
+
+        (dimension == other_dimension).then(|| factor / other_factor)
+    }
+
+    /// Size of this unit in the smallest unit of its dimension (i.e., pieces, grams, liters)
+    fn in_base_unit(self) -> (Dimension, Decimal) {
+        match self {
+            Self::Piece => (Dimension::Count, Decimal::ONE),
+            Self::Kilogram => (Dimension::Mass, Decimal::ONE_THOUSAND),
+            Self::Gram => (Dimension::Mass, Decimal::ONE),
+            Self::Liter => (Dimension::Volume, Decimal::ONE),
+            Self::Pound => (Dimension::Mass, Decimal::new(45359237, 5)),
+        }
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Piece => write!(f, "pcs"),
+            Self::Kilogram => write!(f, "kg"),
+            Self::Gram => write!(f, "g"),
+            Self::Liter => write!(f, "l"),
+            Self::Pound => write!(f, "lb"),
+        }
+    }
+}
+
+/// Where a piece of data of a receipt was taken from,
+/// for receipts assembled from more than one source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataSource {
+    /// Structured data from the API of the store
+    Api,
+    /// Receipt as printed, in HTML
+    Html,
+}
+
+/// Sources that the fields of a receipt line were taken from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemSources {
+    pub barcode: DataSource,
+    pub tax: DataSource,
+    pub discounts: DataSource,
+}
+
+impl ItemSources {
+    /// All fields taken from the same source
+    pub fn all(source: DataSource) -> Self {
+        Self {
+            barcode: source,
+            tax: source,
+            discounts: source,
+        }
+    }
+}
+
+/// Sources that the totals of a receipt were taken from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceiptSources {
+    pub taxes: DataSource,
+    pub total_discount: DataSource,
+    pub tenders: DataSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ReceiptItem {
     /// Price per 1 of quantity
-    pub unit_price: f64,
-    /// Quantity is a float for weight-based products
-    pub quantity: f64,
-    pub is_weight: bool,
+    pub unit_price: Money,
+    /// Quantity is fractional for weight-based products
+    pub quantity: Decimal,
+    /// Unit of the quantity, which is not a piece for products sold by weight or volume
+    #[serde(default)]
+    pub unit: Unit,
     pub name: String,
     pub barcode: String,
     pub discounts: Vec<Discount>,
+    #[serde(default)]
+    pub tax: Option<Tax>,
+    #[serde(default)]
+    pub kind: ItemKind,
+    /// Set if the receipt was assembled from more than one source
+    #[serde(default)]
+    pub sources: Option<ItemSources>,
 }
 
-#[derive(Debug, PartialEq)]
+impl ReceiptItem {
+    /// Price of the whole line without discounts, rounded like in the receipt
+    pub fn total_price(&self) -> Money {
+        (self.unit_price.clone() * self.quantity).round()
+    }
+
+    /// Sum of all the discounts applied to this line
+    pub fn total_discount(&self) -> Result<Money> {
+        Money::sum(
+            self.discounts.iter().map(|discount| &discount.amount),
+            &self.unit_price.currency,
+        )
+        .map_err(Into::into)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ReceiptDetailed {
     pub id: String,
     pub items: Vec<ReceiptItem>,
     pub date: NaiveDateTime,
     pub currency: Currency,
     pub store: Store,
+    #[serde(default)]
+    pub taxes: Vec<TaxSummary>,
+    /// Total paid, as printed in the receipt
+    #[serde(default)]
+    pub total: Option<Money>,
+    /// Total of all discounts, as printed in the receipt
+    #[serde(default)]
+    pub total_discount: Option<Money>,
+    /// Language the receipt is printed in (e.g., "sv")
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub tenders: Vec<Tender>,
+    /// Set if the receipt was assembled from more than one source
+    #[serde(default)]
+    pub sources: Option<ReceiptSources>,
+}
+
+/// Product of a receipt, along with the deposits paid for its containers
+#[derive(Debug, PartialEq)]
+pub struct ProductLine<'a> {
+    pub product: &'a ReceiptItem,
+    pub deposits: Vec<&'a ReceiptItem>,
+}
+
+impl ProductLine<'_> {
+    /// Sum of all the deposits paid for this product
+    pub fn total_deposit(&self) -> Result<Money> {
+        Money::sum(
+            self.deposits.iter().map(|deposit| deposit.total_price()),
+            &self.product.unit_price.currency,
+        )
+        .map_err(Into::into)
+    }
+}
+
+/// Comparison between the items of a receipt and its printed totals
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reconciliation {
+    /// Sum of the price of all items, minus their discounts
+    pub items_total: Money,
+    pub printed_total: Option<Money>,
+    /// Sum of the discounts of all items
+    pub items_discount: Money,
+    pub printed_discount: Option<Money>,
+}
+
+impl Reconciliation {
+    /// Whether the items match all the totals printed in the receipt;
+    /// totals that are not printed are assumed to match
+    pub fn is_balanced(&self) -> bool {
+        let matches = |computed: &Money, printed: &Option<Money>| {
+            printed
+                .as_ref()
+                .map_or(true, |printed| computed.amount == printed.amount)
+        };
+
+        matches(&self.items_total, &self.printed_total)
+            && matches(&self.items_discount, &self.printed_discount)
+    }
+}
+
+impl ReceiptDetailed {
+    /// Check the sum of the items and of their discounts against the totals printed
+    /// in the receipt, to detect items or discounts that were not parsed correctly
+    pub fn reconcile(&self) -> Result<Reconciliation> {
+        let items_price = Money::sum(
+            self.items.iter().map(ReceiptItem::total_price),
+            &self.currency,
+        )?;
+        let items_discount = Money::sum(
+            self.items
+                .iter()
+                .map(ReceiptItem::total_discount)
+                .collect::<Result<Vec<_>>>()?,
+            &self.currency,
+        )?;
+
+        Ok(Reconciliation {
+            items_total: items_price.checked_sub(&items_discount)?,
+            printed_total: self.total.clone(),
+            items_discount,
+            printed_discount: self.total_discount.clone(),
+        })
+    }
+
+    /// Split the items into products, each with the deposit lines printed right after it,
+    /// and all the other lines (deposit returns, bags, services and deposits
+    /// without a product before them)
+    pub fn product_lines(&self) -> (Vec<ProductLine<'_>>, Vec<&ReceiptItem>) {
+        let mut products: Vec<ProductLine> = vec![];
+        let mut others = vec![];
+        let mut last_is_product = false;
+
+        for item in &self.items {
+            match item.kind {
+                ItemKind::Product => products.push(ProductLine {
+                    product: item,
+                    deposits: vec![],
+                }),
+                ItemKind::Deposit if last_is_product => {
+                    if let Some(line) = products.last_mut() {
+                        line.deposits.push(item);
+                    }
+                }
+                _ => others.push(item),
+            }
+            // a product can have several deposits (e.g., a crate and its bottles)
+            last_is_product = item.kind == ItemKind::Product
+                || (item.kind == ItemKind::Deposit && last_is_product);
+        }
+
+        (products, others)
+    }
+
+    /// Fill in the tax rate of each item from the tax summary table, based on its category
+    pub fn fill_tax_rates(&mut self) {
+        for tax in self.items.iter_mut().filter_map(|item| item.tax.as_mut()) {
+            if tax.rate.is_none() {
+                tax.rate = self
+                    .taxes
+                    .iter()
+                    .find(|summary| summary.category == tax.category)
+                    .map(|summary| summary.rate);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use rust_decimal::Decimal;
+
+    use crate::{test_util, ItemKind, ReceiptItem, Unit};
+
+    fn item(name: &str, unit_price: i64, kind: ItemKind) -> ReceiptItem {
+        ReceiptItem {
+            kind,
+            ..test_util::item(name, unit_price)
+        }
+    }
+
+    #[test]
+    fn test_item_kind_from_description() {
+        assert_eq!(
+            ItemKind::from_description("Pfand 0,25 EUR", false),
+            ItemKind::Deposit
+        );
+        assert_eq!(
+            ItemKind::from_description("Pfand", true),
+            ItemKind::DepositReturn
+        );
+        assert_eq!(
+            ItemKind::from_description("Leergut", true),
+            ItemKind::DepositReturn
+        );
+        assert_eq!(ItemKind::from_description("Pant", false), ItemKind::Deposit);
+        assert_eq!(ItemKind::from_description("Bärkasse", false), ItemKind::Bag);
+        assert_eq!(
+            ItemKind::from_description("Pantoffeln", false),
+            ItemKind::Product
+        );
+        assert_eq!(
+            ItemKind::from_description("Gouda i skivor", false),
+            ItemKind::Product
+        );
+        assert_eq!(ItemKind::from_description("Tüte", false), ItemKind::Bag);
+        // "Kasse" is also the till in German
+        assert_eq!(
+            ItemKind::from_description("Kasse 3 Storno", false),
+            ItemKind::Product
+        );
+        assert_eq!(
+            ItemKind::from_description("Tütensuppe Huhn", false),
+            ItemKind::Product
+        );
+        assert_eq!(
+            ItemKind::from_description("Servicewagen", false),
+            ItemKind::Product
+        );
+    }
+
+    #[test]
+    fn test_product_lines() -> Result<()> {
+        let receipt = test_util::receipt(vec![
+            item("Pfand", 25, ItemKind::Deposit),
+            item("Mineralwasser", 19, ItemKind::Product),
+            item("Pfand", 25, ItemKind::Deposit),
+            item("Bier", 79, ItemKind::Product),
+            item("Pfand", 8, ItemKind::Deposit),
+            item("Pfand", 150, ItemKind::Deposit),
+            item("Tragetasche", 20, ItemKind::Bag),
+            item("Pfand", 25, ItemKind::Deposit),
+            item("Leergut", -75, ItemKind::DepositReturn),
+        ]);
+
+        let (products, others) = receipt.product_lines();
+        assert_eq!(products.len(), 2);
+        assert_eq!(products[0].product.name, "Mineralwasser");
+        assert_eq!(products[0].total_deposit()?.amount, Decimal::new(25, 2));
+        assert_eq!(products[1].product.name, "Bier");
+        assert_eq!(products[1].total_deposit()?.amount, Decimal::new(158, 2));
+
+        let others: Vec<_> = others.iter().map(|item| item.kind).collect();
+        assert_eq!(
+            others,
+            vec![
+                ItemKind::Deposit,
+                ItemKind::Bag,
+                ItemKind::Deposit,
+                ItemKind::DepositReturn
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unit_factor_to() {
+        assert_eq!(
+            Unit::Kilogram.factor_to(Unit::Gram),
+            Some(Decimal::ONE_THOUSAND)
+        );
+        assert_eq!(
+            Unit::Gram.factor_to(Unit::Kilogram),
+            Some(Decimal::new(1, 3))
+        );
+        assert_eq!(
+            Unit::Pound.factor_to(Unit::Kilogram),
+            Some(Decimal::new(45359237, 8))
+        );
+        assert_eq!(Unit::Liter.factor_to(Unit::Liter), Some(Decimal::ONE));
+        assert_eq!(Unit::Liter.factor_to(Unit::Kilogram), None);
+        assert_eq!(Unit::Piece.factor_to(Unit::Gram), None);
+
+        assert_eq!(Unit::from_symbol("KG"), Some(Unit::Kilogram));
+        assert_eq!(Unit::from_symbol("x"), None);
+        assert_eq!(Unit::from_name("Kilograms"), Some(Unit::Kilogram));
+    }
 }
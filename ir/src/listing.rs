@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use chrono::NaiveDate;
+
+use crate::{ReceiptSummary, StoreApi};
+
+/// Criteria to filter receipts by when listing them; unset criteria match every receipt
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReceiptFilter {
+    /// Earliest date of the receipts, inclusive
+    pub from: Option<NaiveDate>,
+    /// Latest date of the receipts, inclusive
+    pub to: Option<NaiveDate>,
+    pub store_id: Option<String>,
+}
+
+impl ReceiptFilter {
+    pub fn matches(&self, receipt: &ReceiptSummary) -> bool {
+        let date = receipt.date.date();
+        self.from.map_or(true, |from| date >= from)
+            && self.to.map_or(true, |to| date <= to)
+            && self
+                .store_id
+                .as_ref()
+                .map_or(true, |store_id| receipt.store_id.as_ref() == Some(store_id))
+    }
+
+    /// Whether all receipts older than this one cannot match the filter either
+    fn is_past_range(&self, receipt: &ReceiptSummary) -> bool {
+        self.from.is_some_and(|from| receipt.date.date() < from)
+    }
+}
+
+/// A page of receipts, ordered from newest to oldest
+#[derive(Debug, Default, PartialEq)]
+pub struct ReceiptPage {
+    pub receipts: Vec<ReceiptSummary>,
+    /// Whether there are more (older) receipts in the following pages
+    pub has_more: bool,
+}
+
+/// Iterator over the receipts of a [`StoreApi`] that match a filter,
+/// which only fetches the next page when needed
+pub struct Receipts<'a, S: StoreApi + ?Sized> {
+    api: &'a S,
+    filter: ReceiptFilter,
+    next_page: u32,
+    has_more: bool,
+    buffer: VecDeque<ReceiptSummary>,
+}
+
+impl<'a, S: StoreApi + ?Sized> Receipts<'a, S> {
+    pub(crate) fn new(api: &'a S, filter: ReceiptFilter) -> Self {
+        Self {
+            api,
+            filter,
+            next_page: 1,
+            has_more: true,
+            buffer: VecDeque::new(),
+        }
+    }
+}
+
+impl<S: StoreApi + ?Sized> Iterator for Receipts<'_, S> {
+    type Item = Result<ReceiptSummary>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(receipt) = self.buffer.pop_front() {
+                if self.filter.is_past_range(&receipt) {
+                    // receipts are ordered from newest to oldest, so nothing else will match
+                    self.buffer.clear();
+                    self.has_more = false;
+                    return None;
+                }
+                if self.filter.matches(&receipt) {
+                    return Some(Ok(receipt));
+                }
+                continue;
+            }
+
+            if !self.has_more {
+                return None;
+            }
+
+            match self.api.get_receipts_page(self.next_page) {
+                Ok(page) => {
+                    self.next_page += 1;
+                    self.has_more = page.has_more && !page.receipts.is_empty();
+                    self.buffer.extend(page.receipts);
+                }
+                Err(error) => {
+                    self.has_more = false;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use anyhow::{anyhow, Result};
+    use chrono::NaiveDate;
+
+    use crate::{
+        test_util::money, ReceiptDetailed, ReceiptFilter, ReceiptPage, ReceiptSummary, StoreApi,
+    };
+
+    /// Store with one receipt per day, going back from 2024-01-10, two per page
+    struct DailyStore {
+        pages_fetched: Cell<u32>,
+    }
+
+    impl StoreApi for DailyStore {
+        fn get_receipts_page(&self, page: u32) -> Result<ReceiptPage> {
+            self.pages_fetched.set(self.pages_fetched.get() + 1);
+            let receipts = (0..2)
+                .map(|i| {
+                    let day = 10 - ((page - 1) * 2 + i);
+                    ReceiptSummary {
+                        id: format!("receipt-{}", day),
+                        date: NaiveDate::from_ymd_opt(2024, 1, day)
+                            .unwrap()
+                            .and_hms_opt(12, 0, 0)
+                            .unwrap(),
+                        total_amount: money(100),
+                        articles_count: None,
+                        store_id: Some(if day % 2 == 0 { "even" } else { "odd" }.to_owned()),
+                    }
+                })
+                .collect();
+
+            Ok(ReceiptPage {
+                receipts,
+                has_more: page < 5,
+            })
+        }
+
+        fn get_specific_receipt(&self, receipt: &ReceiptSummary) -> Result<ReceiptDetailed> {
+            Err(anyhow!(
+                "receipt {} is not available in this store",
+                receipt.id
+            ))
+        }
+    }
+
+    fn ids(store: &DailyStore, filter: ReceiptFilter) -> Result<Vec<String>> {
+        store
+            .list_receipts(filter)
+            .map(|receipt| receipt.map(|receipt| receipt.id))
+            .collect()
+    }
+
+    #[test]
+    fn test_list_receipts_all_pages() -> Result<()> {
+        let store = DailyStore {
+            pages_fetched: Cell::new(0),
+        };
+        assert_eq!(ids(&store, ReceiptFilter::default())?.len(), 10);
+        assert_eq!(store.pages_fetched.get(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_receipts_filtered() -> Result<()> {
+        let store = DailyStore {
+            pages_fetched: Cell::new(0),
+        };
+        let filter = ReceiptFilter {
+            from: NaiveDate::from_ymd_opt(2024, 1, 4),
+            to: NaiveDate::from_ymd_opt(2024, 1, 8),
+            store_id: Some("even".to_owned()),
+        };
+        assert_eq!(
+            ids(&store, filter)?,
+            vec!["receipt-8", "receipt-6", "receipt-4"]
+        );
+        // the page with receipts from the 4th also has one from the 3rd, so it stops there
+        assert_eq!(store.pages_fetched.get(), 4);
+
+        Ok(())
+    }
+}
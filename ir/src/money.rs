@@ -0,0 +1,223 @@
+use std::{
+    borrow::Borrow,
+    fmt::Display,
+    ops::{Mul, Neg},
+};
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::Error, Currency};
+
+/// Number of decimal places of the minor unit (e.g., cents) of a currency
+pub const MINOR_UNIT_DECIMAL_PLACES: u32 = 2;
+
+/// Parse a decimal number that might use either a comma or a dot as decimal separator,
+/// as is common in receipts (e.g., "79,90" or "-7.92")
+pub fn parse_decimal(value: &str) -> Result<Decimal, Error> {
+    value
+        .trim()
+        .replace(',', ".")
+        .parse()
+        .map_err(|_| Error::InvalidDecimal(value.to_string()))
+}
+
+/// An exact amount of money in a given currency
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Money {
+    pub amount: Decimal,
+    pub currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: Currency) -> Self {
+        Self { amount, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Self::new(Decimal::ZERO, currency)
+    }
+
+    pub fn parse(value: &str, currency: Currency) -> Result<Self, Error> {
+        Ok(Self::new(parse_decimal(value)?, currency))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount.is_zero()
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::new(self.amount.abs(), self.currency.clone())
+    }
+
+    /// Round to the minor unit of the currency, with halves rounded away from zero,
+    /// which is how receipts round line totals (e.g., 0.957 kg x 67.90 = 64.98)
+    pub fn round(&self) -> Self {
+        Self::new(
+            self.amount.round_dp_with_strategy(
+                MINOR_UNIT_DECIMAL_PLACES,
+                RoundingStrategy::MidpointAwayFromZero,
+            ),
+            self.currency.clone(),
+        )
+    }
+
+    /// Split this amount into `parts` amounts rounded to the minor unit of the currency,
+    /// whose sum is exactly this amount (after rounding).
+    /// Each part gets the amount rounded towards zero, and the remaining minor units
+    /// are then given, one by one, to the first parts.
+    pub fn split(&self, parts: u32) -> Vec<Money> {
+        if parts == 0 {
+            return vec![];
+        }
+
+        let total = self.round().amount;
+        let minor_unit = if total.is_sign_negative() {
+            Decimal::new(-1, MINOR_UNIT_DECIMAL_PLACES)
+        } else {
+            Decimal::new(1, MINOR_UNIT_DECIMAL_PLACES)
+        };
+        let share = (total / Decimal::from(parts))
+            .round_dp_with_strategy(MINOR_UNIT_DECIMAL_PLACES, RoundingStrategy::ToZero);
+        let mut remainder = total - share * Decimal::from(parts);
+
+        (0..parts)
+            .map(|_| {
+                let mut amount = share;
+                if !remainder.is_zero() {
+                    amount += minor_unit;
+                    remainder -= minor_unit;
+                }
+                Money::new(amount, self.currency.clone())
+            })
+            .collect()
+    }
+
+    /// Sum all amounts, which must be in the given currency
+    pub fn sum<M: Borrow<Money>>(
+        amounts: impl IntoIterator<Item = M>,
+        currency: &Currency,
+    ) -> Result<Money, Error> {
+        amounts
+            .into_iter()
+            .try_fold(Money::zero(currency.clone()), |acc, amount| {
+                acc.checked_add(amount.borrow())
+            })
+    }
+
+    /// Add two amounts, which must be in the same currency
+    pub fn checked_add(&self, other: &Money) -> Result<Money, Error> {
+        self.check_same_currency(other)?;
+        Ok(Money::new(
+            self.amount + other.amount,
+            self.currency.clone(),
+        ))
+    }
+
+    /// Subtract two amounts, which must be in the same currency
+    pub fn checked_sub(&self, other: &Money) -> Result<Money, Error> {
+        self.check_same_currency(other)?;
+        Ok(Money::new(
+            self.amount - other.amount,
+            self.currency.clone(),
+        ))
+    }
+
+    fn check_same_currency(&self, other: &Money) -> Result<(), Error> {
+        if self.currency.id != other.currency.id {
+            return Err(Error::CurrencyMismatch(
+                self.currency.id.clone(),
+                other.currency.id.clone(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.*} {}",
+            MINOR_UNIT_DECIMAL_PLACES as usize, self.amount, self.currency.symbol
+        )
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Self::Output {
+        Money::new(-self.amount, self.currency)
+    }
+}
+
+impl Mul<Decimal> for Money {
+    type Output = Money;
+
+    fn mul(self, rhs: Decimal) -> Self::Output {
+        Money::new(self.amount * rhs, self.currency)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rust_decimal::Decimal;
+
+    use crate::{test_util, Currency};
+
+    use super::{parse_decimal, Money};
+
+    fn sek(amount: &str) -> Money {
+        Money::parse(
+            amount,
+            Currency {
+                id: "SEK".to_owned(),
+                symbol: "kr".to_owned(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(parse_decimal("79,90").unwrap(), Decimal::new(7990, 2));
+        assert_eq!(parse_decimal(" -7.92 ").unwrap(), Decimal::new(-792, 2));
+        assert!(parse_decimal("7,9,2").is_err());
+    }
+
+    #[test]
+    fn test_money_round() {
+        assert_eq!(
+            (sek("67,90") * parse_decimal("0,957").unwrap()).round(),
+            sek("64,98")
+        );
+        assert_eq!(sek("0,125").round(), sek("0,13"));
+        assert_eq!(sek("-0,125").round(), sek("-0,13"));
+    }
+
+    #[test]
+    fn test_money_checked_arithmetic() {
+        assert_eq!(sek("1,50").checked_add(&sek("0,25")).unwrap(), sek("1,75"));
+        assert_eq!(sek("1,50").checked_sub(&sek("0,25")).unwrap(), sek("1,25"));
+
+        let eur = test_util::money(100);
+        assert!(sek("1,00").checked_add(&eur).is_err());
+        assert!(Money::sum([sek("1,00"), eur], &sek("0").currency).is_err());
+    }
+
+    #[test]
+    fn test_money_split() {
+        assert_eq!(sek("7,92").split(2), vec![sek("3,96"), sek("3,96")]);
+        assert_eq!(
+            sek("1,00").split(3),
+            vec![sek("0,34"), sek("0,33"), sek("0,33")]
+        );
+        assert_eq!(sek("-0,05").split(2), vec![sek("-0,03"), sek("-0,02")]);
+        assert_eq!(
+            sek("0,01").split(3),
+            vec![sek("0,01"), sek("0,00"), sek("0,00")]
+        );
+        assert!(sek("1,00").split(0).is_empty());
+    }
+}
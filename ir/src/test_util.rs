@@ -0,0 +1,59 @@
+//! Receipts and their parts for tests, shared by the crates that read receipts.
+//! Amounts are given in cents of euro, and everything else is filled with defaults
+//! that can be changed with the struct update syntax.
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::{Currency, ItemKind, Money, ReceiptDetailed, ReceiptItem, Store, Unit};
+
+pub fn eur() -> Currency {
+    Currency {
+        id: "EUR".to_owned(),
+        symbol: "€".to_owned(),
+    }
+}
+
+pub fn money(cents: i64) -> Money {
+    Money::new(Decimal::new(cents, 2), eur())
+}
+
+/// One piece of a product, without barcode, discounts or tax
+pub fn item(name: &str, unit_price: i64) -> ReceiptItem {
+    ReceiptItem {
+        unit_price: money(unit_price),
+        quantity: Decimal::ONE,
+        unit: Unit::Piece,
+        name: name.to_owned(),
+        barcode: String::new(),
+        discounts: vec![],
+        tax: None,
+        kind: ItemKind::Product,
+        sources: None,
+    }
+}
+
+/// Receipt of 2024-01-01 from an example store, without any of the printed totals
+pub fn receipt(items: Vec<ReceiptItem>) -> ReceiptDetailed {
+    ReceiptDetailed {
+        id: "test-id".to_owned(),
+        items,
+        date: NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap(),
+        currency: eur(),
+        store: Store {
+            id: "STORE123".to_owned(),
+            name: "Example Store".to_owned(),
+            address: vec![],
+            country: None,
+        },
+        taxes: vec![],
+        total: None,
+        total_discount: None,
+        language: None,
+        tenders: vec![],
+        sources: None,
+    }
+}
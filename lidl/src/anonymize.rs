@@ -0,0 +1,294 @@
+//! Remove personal data from Lidl receipts, so that they can be shared (e.g., attached to
+//! a bug report or added to the tests of the parsers) without giving away where and how
+//! the purchase was made.
+//!
+//! Only the personal data is replaced, by `CENSORED`, and everything else is kept as-is,
+//! so that the anonymized receipt is parsed just like the original one.
+
+use serde_json::Value;
+
+/// Text that personal data is replaced with
+const CENSORED: &str = "CENSORED";
+
+/// Attributes of the HTML receipt that hold personal data
+const HTML_SENSITIVE_ATTRIBUTES: &[&str] = &["data-promotion-id", "data-return-code"];
+
+/// Fields of the JSON receipt that hold personal data, anywhere in the receipt
+const JSON_SENSITIVE_FIELDS: &[&str] = &[
+    "barCode",
+    "sequenceNumber",
+    "workstation",
+    "promotionId",
+    "returnCode",
+];
+
+/// Fields of the stores and payments of the JSON receipt that are kept, since the
+/// parser needs them; all other fields of these are censored
+const JSON_STORE_FIELDS: &[&str] = &["id"];
+const JSON_PAYMENT_FIELDS: &[&str] = &["type", "description", "amount"];
+
+/// Anonymize a receipt in HTML, as returned by the v3 API of Lidl Plus:
+/// - the header (the name and address of the store) is censored;
+/// - in the payment details, everything that has a number is censored (e.g., card
+///   numbers, terminal ids and dates), except amounts of money;
+/// - the return code and promotion ids are censored.
+pub fn anonymize_html_receipt(html: &str) -> String {
+    let mut anonymized = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find("<span") {
+        let Some(tag_length) = rest[start..].find('>') else {
+            break;
+        };
+        let tag_end = start + tag_length + 1;
+        let text_end = rest[tag_end..]
+            .find('<')
+            .map_or(rest.len(), |length| tag_end + length);
+
+        let tag = &rest[start..tag_end];
+        let text = &rest[tag_end..text_end];
+        anonymized.push_str(&rest[..start]);
+        anonymized.push_str(&censor_attributes(tag));
+        anonymized.push_str(&censor_line(attribute(tag, "id").unwrap_or_default(), text));
+
+        rest = &rest[text_end..];
+    }
+    anonymized.push_str(rest);
+
+    anonymized
+}
+
+/// Anonymize a receipt in JSON, as returned by the v2 or v3 APIs of Lidl Plus:
+/// - the store is censored, except for its id;
+/// - the payments are censored, except for their type, description and amount;
+/// - the barcode of the receipt, its till and sequence number, and promotion ids
+///   are censored;
+/// - the HTML receipt of the v3 API is anonymized like [`anonymize_html_receipt`].
+pub fn anonymize_json_receipt(json: &str) -> Result<String, serde_json::Error> {
+    let mut receipt: Value = serde_json::from_str(json)?;
+    anonymize_json_value(&mut receipt);
+
+    serde_json::to_string_pretty(&receipt)
+}
+
+fn anonymize_json_value(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                match key.as_str() {
+                    key if JSON_SENSITIVE_FIELDS.contains(&key) => censor_json_value(value),
+                    "htmlPrintedReceipt" => {
+                        if let Value::String(html) = value {
+                            *html = anonymize_html_receipt(html);
+                        }
+                    }
+                    "store" => censor_json_fields(value, JSON_STORE_FIELDS),
+                    "payments" => {
+                        for payment in value.as_array_mut().into_iter().flatten() {
+                            censor_json_fields(payment, JSON_PAYMENT_FIELDS);
+                        }
+                    }
+                    _ => anonymize_json_value(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(anonymize_json_value),
+        _ => {}
+    }
+}
+
+/// Censor all the fields of an object except `kept_fields`
+fn censor_json_fields(value: &mut Value, kept_fields: &[&str]) {
+    if let Value::Object(fields) = value {
+        for (key, value) in fields.iter_mut() {
+            if !kept_fields.contains(&key.as_str()) {
+                censor_json_value(value);
+            }
+        }
+    }
+}
+
+/// Censor the strings of a value, keeping its structure; numbers and booleans are kept,
+/// since they are not personal on their own and the parsers might need their type
+fn censor_json_value(value: &mut Value) {
+    match value {
+        Value::String(string) if !string.is_empty() => *string = CENSORED.to_string(),
+        Value::Array(values) => values.iter_mut().for_each(censor_json_value),
+        Value::Object(fields) => fields.values_mut().for_each(censor_json_value),
+        _ => {}
+    }
+}
+
+fn censor_attributes(tag: &str) -> String {
+    let mut tag = tag.to_string();
+    for name in HTML_SENSITIVE_ATTRIBUTES {
+        if let Some((start, end)) = attribute_range(&tag, name) {
+            tag.replace_range(start..end, CENSORED);
+        }
+    }
+    tag
+}
+
+/// Censor the text of a line of the receipt, depending on which part of the receipt
+/// it is in, which is given by its id (e.g., "header_line_2")
+fn censor_line(id: &str, text: &str) -> String {
+    if id.starts_with("header_line_") || id.starts_with("return_code_line_") {
+        censor_words(text, |_| true)
+    } else if id.starts_with("purchase_tender_information_") || id.starts_with("purchase_summary_")
+    {
+        censor_words(text, |word| has_digits(word) && !is_amount(word))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Replace the words of a text for which `censor` is true, keeping the whitespace
+/// between them so that the layout of the receipt is kept
+fn censor_words(text: &str, censor: impl Fn(&str) -> bool) -> String {
+    let mut censored = String::with_capacity(text.len());
+    let mut word_start = None;
+    for (index, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (c.is_whitespace(), word_start) {
+            (false, None) => word_start = Some(index),
+            (true, Some(start)) => {
+                let word = &text[start..index];
+                censored.push_str(if censor(word) { CENSORED } else { word });
+                word_start = None;
+            }
+            _ => {}
+        }
+        if c.is_whitespace() && index < text.len() {
+            censored.push(c);
+        }
+    }
+    censored
+}
+
+/// Whether a word has digits, ignoring HTML character references (e.g., "&#39;")
+fn has_digits(word: &str) -> bool {
+    let mut in_reference = false;
+    word.chars().any(|c| {
+        match c {
+            '&' => in_reference = true,
+            ';' => in_reference = false,
+            _ => {}
+        }
+        !in_reference && c.is_ascii_digit()
+    })
+}
+
+/// Whether a word is an amount of money (e.g., "479,24" or "-7.92")
+fn is_amount(word: &str) -> bool {
+    let digits = word.strip_prefix('-').unwrap_or(word);
+    match digits.rsplit_once([',', '.']) {
+        Some((units, cents)) => {
+            // the units might have thousands separators (e.g., "1.234,56")
+            !units.is_empty()
+                && units.chars().all(|c| c.is_ascii_digit() || c == '.')
+                && cents.len() == 2
+                && cents.chars().all(|c| c.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+/// Value of an attribute of an HTML tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    attribute_range(tag, name).map(|(start, end)| &tag[start..end])
+}
+
+/// Position of the value of an attribute in an HTML tag
+fn attribute_range(tag: &str, name: &str) -> Option<(usize, usize)> {
+    let prefix = format!(" {}=\"", name);
+    let start = tag.find(&prefix)? + prefix.len();
+    let end = start + tag[start..].find('"')?;
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use ir::{test_util, ReceiptDetailed};
+    use serde_json::Value;
+
+    use crate::html_receipt::parse_html_receipt;
+
+    use super::{anonymize_html_receipt, anonymize_json_receipt};
+
+    #[test]
+    fn test_anonymize_html_receipt() -> Result<()> {
+        let html = r#"<pre><span class="header" data-till-country="SE" data-receipt-language="sv"><span id="header_line_1">Lidl Storgatan</span>
+<span id="header_line_2">  Storgatan 1, 123 45 Stockholm  </span>
+</span><span class="purchase_list"><span id="purchase_list_line_1" class="currency" data-currency="kr">SEK</span>
+<span id="purchase_list_line_2" class="article" data-art-id="0051496" data-art-quantity="2" data-unit-price="79,90" data-tax-type="C" data-art-description="Grytbitar">Grytbitar           79,90 x   2   159,80 C</span>
+<span id="purchase_list_line_3" class="discount" data-promotion-id="100001234">     Lidl Plus-rabatt              -7,92</span>
+</span><span class="purchase_summary"><span id="purchase_summary_1">ATT BETALA                        151,88</span>
+<span id="purchase_summary_2" data-tender-description="Kort">Kort                              151,88</span>
+</span><span class="purchase_tender_information"><span id="purchase_tender_information_1">Cardholder&#39;s Receipt</span>
+<span id="purchase_tender_information_2">VISA         ************1234</span>
+<span id="purchase_tender_information_3">2024-01-13 10:30    TID 87654321</span>
+<span id="purchase_tender_information_4">TOTAL                         SEK 151,88</span>
+<span id="purchase_tender_information_5">Total rabatt                         7,92</span>
+</span><span class="return_code"><span id="return_code_line_1" data-return-code="0123456789"></span>
+<span id="return_code_line_2">0123 4567 89</span>
+</span></pre>"#;
+
+        let anonymized = anonymize_html_receipt(html);
+        for personal in [
+            "Storgatan",
+            "Stockholm",
+            "100001234",
+            "1234",
+            "87654321",
+            "2024-01-13",
+            "0123456789",
+        ] {
+            assert!(
+                !anonymized.contains(personal),
+                "{} is not censored",
+                personal
+            );
+        }
+        assert!(anonymized.contains(
+            r#"<span id="header_line_2">  CENSORED CENSORED CENSORED CENSORED CENSORED  </span>"#
+        ));
+        assert!(anonymized.contains("Cardholder&#39;s Receipt"));
+        assert!(anonymized.contains("Total rabatt                         7,92"));
+
+        // the anonymized receipt is parsed just like the original one
+        let parse = |html| {
+            let ReceiptDetailed {
+                id, date, store, ..
+            } = test_util::receipt(vec![]);
+            parse_html_receipt(id, date, store, html)
+        };
+        let original = parse(html)?;
+        let anonymized = parse(&anonymized)?;
+        assert_eq!(anonymized.items.len(), original.items.len());
+        assert_eq!(anonymized.total, original.total);
+        assert_eq!(anonymized.total_discount, original.total_discount);
+        assert_eq!(anonymized.tenders, original.tenders);
+        assert_eq!(
+            anonymized.items[0].discounts[0].promotion_id.as_deref(),
+            Some("CENSORED")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_anonymize_json_receipt() -> Result<()> {
+        let json = include_str!("../test/api/ticket_v3_with_v2.json");
+        let anonymized: Value = serde_json::from_str(&anonymize_json_receipt(json)?)?;
+        let original: Value = serde_json::from_str(json)?;
+
+        assert_eq!(anonymized["id"], original["id"]);
+        assert_eq!(anonymized["store"]["id"], original["store"]["id"]);
+        assert_eq!(anonymized["store"]["name"], "CENSORED");
+        assert!(anonymized["htmlPrintedReceipt"]
+            .as_str()
+            .is_some_and(|html| html.contains("CENSORED")));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+//! Archive of the raw responses of the Lidl Plus API, so that receipts can be parsed again
+//! without network (e.g., to reproduce a parser bug, or to import a receipt again after
+//! Lidl deletes it from the app).
+//!
+//! The archive is a directory with this layout:
+//! - `pages/<page>.json`: the pages of the list of receipts;
+//! - `receipts/<receipt id>/summary.json`: the receipt as it was listed, to list it offline;
+//! - `receipts/<receipt id>/v2.json`: the receipt from the v2 API, if Lidl has it;
+//! - `receipts/<receipt id>/v3.json`: the receipt from the v3 API, with its HTML.
+
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+use anyhow::Result;
+use ir::{ReceiptPage, ReceiptSummary, StoreApi};
+
+use crate::error::Error;
+
+/// Version of the tickets API that a receipt response comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ApiVersion {
+    V2,
+    V3,
+}
+
+impl ApiVersion {
+    fn file_name(self) -> &'static str {
+        match self {
+            Self::V2 => "v2.json",
+            Self::V3 => "v3.json",
+        }
+    }
+}
+
+/// Directory where the raw responses of the Lidl Plus API are saved, which can also be
+/// read as a store of its own, without network
+#[derive(Debug, Clone)]
+pub struct ResponseArchive {
+    dir: PathBuf,
+}
+
+impl ResponseArchive {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub(crate) fn save_receipts_page(&self, page: u32, body: &str) -> Result<()> {
+        let dir = self.dir.join("pages");
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(format!("{}.json", page)), body)?;
+        Ok(())
+    }
+
+    pub(crate) fn save_receipt_response(
+        &self,
+        receipt: &ReceiptSummary,
+        version: ApiVersion,
+        body: &str,
+    ) -> Result<()> {
+        let dir = self.receipt_dir(&receipt.id)?;
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join("summary.json"),
+            serde_json::to_string_pretty(receipt)?,
+        )?;
+        fs::write(dir.join(version.file_name()), body)?;
+        Ok(())
+    }
+
+    /// Raw response of an archived receipt, if it was archived from this API version
+    fn read_receipt_response(&self, id: &str, version: ApiVersion) -> Result<Option<String>> {
+        match fs::read_to_string(self.receipt_dir(id)?.join(version.file_name())) {
+            Ok(body) => Ok(Some(body)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Summaries of all the archived receipts, from newest to oldest
+    pub fn archived_receipts(&self) -> Result<Vec<ReceiptSummary>> {
+        let entries = match fs::read_dir(self.dir.join("receipts")) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut receipts = vec![];
+        for entry in entries {
+            let summary = match fs::read_to_string(entry?.path().join("summary.json")) {
+                Ok(summary) => summary,
+                Err(error) if error.kind() == ErrorKind::NotFound => continue,
+                Err(error) => return Err(error.into()),
+            };
+            receipts.push(serde_json::from_str::<ReceiptSummary>(&summary)?);
+        }
+        receipts.sort_by_key(|receipt| std::cmp::Reverse(receipt.date));
+
+        Ok(receipts)
+    }
+
+    /// Directory of a receipt, making sure that its id cannot point outside the archive
+    fn receipt_dir(&self, id: &str) -> Result<PathBuf> {
+        if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+            return Err(Error::InvalidReceiptId(id.to_string()).into());
+        }
+        Ok(self.dir.join("receipts").join(id))
+    }
+}
+
+impl StoreApi for ResponseArchive {
+    fn get_receipts_page(&self, page: u32) -> Result<ReceiptPage> {
+        // all the receipts are read from disk at once, so there is only one page
+        Ok(ReceiptPage {
+            receipts: match page {
+                1 => self.archived_receipts()?,
+                _ => vec![],
+            },
+            has_more: false,
+        })
+    }
+
+    fn get_specific_receipt(&self, receipt: &ReceiptSummary) -> Result<ir::ReceiptDetailed> {
+        let v2 = self.read_receipt_response(&receipt.id, ApiVersion::V2)?;
+        let v3 = self.read_receipt_response(&receipt.id, ApiVersion::V3)?;
+        if v2.is_none() && v3.is_none() {
+            return Err(Error::NotArchived(receipt.id.clone()).into());
+        }
+
+        crate::parse_receipt(v2.as_deref(), v3.as_deref())
+    }
+}
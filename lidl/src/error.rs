@@ -1,3 +1,4 @@
+use reqwest::StatusCode;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -12,4 +13,49 @@ pub enum Error {
     HtmlReceiptParse(&'static str),
     #[error("Could not parse HTML receipt: cannot find attribute {0} in element")]
     HtmlReceiptParseMissingAttr(&'static str),
+    #[error("Lidl did not accept the credentials, which might have expired or been revoked")]
+    Unauthorized,
+    #[error("Too many requests were made to Lidl, please try again later")]
+    RateLimited,
+    #[error("Lidl is having trouble answering requests (HTTP status {0})")]
+    Server(u16),
+    #[error("Lidl answered with an unexpected HTTP status {0}")]
+    UnexpectedStatus(u16),
+    #[error("Lidl answered with an unexpected response: {0}")]
+    UnexpectedPayload(String),
+    #[error("Could not connect to Lidl: {0}")]
+    Connection(String),
+    #[error("Receipt {0} is not in the archive of responses")]
+    NotArchived(String),
+    #[error("Receipt id {0:?} cannot be used as the name of a directory in the archive")]
+    InvalidReceiptId(String),
+}
+
+impl Error {
+    /// Error for a response with an unsuccessful status, if it is not successful
+    pub(crate) fn from_status(status: StatusCode) -> Option<Self> {
+        match status {
+            status if status.is_success() => None,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Some(Self::Unauthorized),
+            StatusCode::TOO_MANY_REQUESTS => Some(Self::RateLimited),
+            status if status.is_server_error() => Some(Self::Server(status.as_u16())),
+            status => Some(Self::UnexpectedStatus(status.as_u16())),
+        }
+    }
+
+    /// Whether Lidl answered the request, but with an unsuccessful status
+    pub fn is_unsuccessful_response(&self) -> bool {
+        matches!(
+            self,
+            Self::Unauthorized | Self::RateLimited | Self::Server(_) | Self::UnexpectedStatus(_)
+        )
+    }
+
+    /// Whether the request that failed with this error might succeed if made again later
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited | Self::Server(_) | Self::Connection(_)
+        )
+    }
 }
@@ -1,119 +1,601 @@
 use chrono::NaiveDateTime;
-use ir::{Currency, Discount, ReceiptDetailed, ReceiptItem, Store};
+use ir::{
+    money::{parse_decimal, MINOR_UNIT_DECIMAL_PLACES},
+    Currency, Discount, DiscountKind, ItemKind, Money, ReceiptDetailed, ReceiptItem, Store, Tax,
+    TaxSummary, Tender, Unit,
+};
+use rust_decimal::{Decimal, RoundingStrategy};
 use scraper::{node::Element, Html, Selector};
 
 use crate::error::Error;
 
-pub(crate) fn parse_html_receipt(
+/// Parse the HTML version of a receipt, as returned by the v3 API of Lidl Plus
+/// (in the `htmlPrintedReceipt` field) or as saved from the app.
+/// The HTML does not reliably include the id, date or store of the receipt,
+/// so these must be given separately; the store is completed with the address and
+/// country printed in the header of the receipt.
+///
+/// Fails on the first line of the receipt that cannot be parsed; see
+/// [`parse_html_receipt_lenient`] to skip such lines instead.
+pub fn parse_html_receipt(
     id: String,
     date: NaiveDateTime,
     store: Store,
     html: &str,
 ) -> Result<ReceiptDetailed, Error> {
+    let parsed = parse_html_receipt_lenient(id, date, store, html)?;
+    match parsed.warnings.into_iter().next() {
+        Some(warning) => Err(warning.error),
+        None => Ok(parsed.receipt),
+    }
+}
+
+/// Receipt parsed from HTML, along with the lines that had to be left out of it
+#[derive(Debug)]
+pub struct LenientReceipt {
+    pub receipt: ReceiptDetailed,
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// Line of the list of products that could not be parsed
+#[derive(Debug)]
+pub struct ParseWarning {
+    /// Id of the line in the HTML (e.g., "purchase_list_line_3")
+    pub line_id: String,
+    /// Raw text of the line, as printed in the receipt
+    pub text: String,
+    /// Index in the items of the receipt where the line would have been
+    pub position: usize,
+    pub error: Error,
+}
+
+/// Like [`parse_html_receipt`], but lines of the list of products that cannot be parsed
+/// (e.g., a discount before any product, or an amount that is not a number) are left out
+/// of the receipt and returned as warnings.
+/// It only fails if the receipt cannot be parsed at all (e.g., it has no currency).
+pub fn parse_html_receipt_lenient(
+    id: String,
+    date: NaiveDateTime,
+    mut store: Store,
+    html: &str,
+) -> Result<LenientReceipt, Error> {
     let dom = Html::parse_document(html);
-    let selector = Selector::parse(r#"span[id^="purchase_list_line_"]"#).unwrap();
+    let header = parse_header(&dom);
+    if store.address.is_empty() {
+        store.address = header.address;
+    }
+    if store.country.is_none() {
+        store.country = header.country;
+    }
 
     let mut currency = None;
     let mut items = vec![];
-    let mut iter = dom.select(&selector).peekable();
-    while let Some(element) = iter.next() {
-        let el = element.value();
-        if let Some(next_element) = iter.peek() {
-            if next_element.value().id() == el.id() {
-                // Lidl Germany has multiple spans with the same ID wrapping different parts of
-                // the same receipt line (probably a bug on their side).
-                // Skip duplicate spans if ID the same.
-                continue;
-            }
+    let mut warnings = vec![];
+    let mut previous_failed = false;
+    for line in select_lines(&dom, r#"span[id^="purchase_list_line_"]"#) {
+        // the discounts of a product that could not be parsed must not be given to
+        // the product before it
+        let is_discount = line.element.classes().any(|class| class == "discount");
+        let item_count = items.len();
+        let result = if previous_failed && is_discount {
+            Err(Error::HtmlReceiptParse(
+                "found discount of a product that could not be parsed",
+            ))
+        } else {
+            parse_purchase_line(&line, &mut currency, &mut items)
+        };
+        previous_failed = match result {
+            // sub-lines of a product (e.g., its weight) do not add an item
+            Ok(()) => previous_failed && items.len() == item_count,
+            Err(_) => previous_failed || !is_discount,
+        };
+
+        if let Err(error) = result {
+            warnings.push(ParseWarning {
+                line_id: line.element.id().unwrap_or_default().to_string(),
+                text: line.text.trim_end().to_string(),
+                position: items.len(),
+                error,
+            });
         }
-        for class in el.classes() {
-            match class {
-                "currency" => {
-                    let symbol = get_attr(el, "data-currency")?.to_string();
-                    let id = element.text().collect::<String>().trim().to_string();
-                    currency = Some(Currency { id, symbol });
-                }
-                "article" => {
-                    // line is a sub-line if its text starts with whitespace
-                    let is_article_subline = element
-                        .text()
-                        .next()
-                        .and_then(|s| s.chars().next())
-                        .map(|c| c.is_whitespace())
-                        .unwrap_or(false);
-                    if is_article_subline {
-                        continue;
+    }
+
+    if let Some(currency) = currency {
+        let taxes = parse_tax_summary(&dom, &currency)?;
+        let mut receipt = ReceiptDetailed {
+            id,
+            items,
+            date,
+            currency: currency.clone(),
+            store,
+            taxes,
+            total: parse_total(&dom, &currency),
+            total_discount: parse_total_discount(&dom, &currency),
+            language: header.language,
+            tenders: parse_tenders(&dom, &currency)?,
+            sources: None,
+        };
+        receipt.fill_tax_rates();
+        Ok(LenientReceipt { receipt, warnings })
+    } else {
+        Err(Error::HtmlReceiptParse(
+            "could not find currency in receipt",
+        ))
+    }
+}
+
+/// Parse a line of the list of products, which might be the currency, a product, or
+/// a discount of the product before it
+fn parse_purchase_line(
+    line: &Line,
+    currency: &mut Option<Currency>,
+    items: &mut Vec<ReceiptItem>,
+) -> Result<(), Error> {
+    let el = line.element;
+    for class in el.classes() {
+        match class {
+            "currency" => {
+                let symbol = get_attr(el, "data-currency")?.to_string();
+                let id = line.text.trim().to_string();
+                *currency = Some(Currency { id, symbol });
+            }
+            "article" => {
+                // line is a sub-line if its text starts with whitespace
+                let is_article_subline = line
+                    .text
+                    .chars()
+                    .next()
+                    .map(|c| c.is_whitespace())
+                    .unwrap_or(false);
+                if is_article_subline {
+                    // e.g., "  0,957 kg x 67,90  SEK/kg" or "  2 x 0,89" after the line
+                    // of the product
+                    if let Some(item) = items.last_mut() {
+                        let art_id = el.attr("data-art-id").map(|id| format!("lidl-{}", id));
+                        if art_id.map_or(true, |art_id| art_id == item.barcode) {
+                            apply_subline(el, &parse_printed_amounts(&line.text), item);
+                        }
                     }
+                    continue;
+                }
+
+                let currency = currency.as_ref().ok_or(Error::HtmlReceiptParse(
+                    "found product but there is no currency before it",
+                ))?;
+                let printed = parse_printed_amounts(&line.text);
 
-                    let id = get_attr(el, "data-art-id")?;
-                    let unit_price = get_attr(el, "data-unit-price").and_then(|price| {
-                        price.replace(',', ".").parse::<f64>().map_err(|_| {
-                            Error::HtmlReceiptParse("cannot parse unit price as float")
+                // the unit is printed in the sub-line of the product, if any, so until then
+                // assume it is sold by the kilogram if the quantity has a decimal separator
+                let attr_quantity = el
+                    .attr("data-art-quantity")
+                    .map(|quantity| {
+                        parse_decimal(quantity).map_err(|_| {
+                            Error::HtmlReceiptParse("cannot parse quantity as decimal")
                         })
-                    })?;
-                    let name = get_attr(el, "data-art-description")?.to_string();
-
-                    // there isn't a is_weight field anymore, so assume it has weight if it has
-                    // decimal separator; also, this attribute might be missing if it is 1
-                    let (quantity, is_weight) = get_attr(el, "data-art-quantity")
-                        .map(|quantity| {
-                            let is_weight = quantity.contains(',');
-                            let quantity =
-                                quantity.replace(',', ".").parse::<f64>().map_err(|_| {
-                                    Error::HtmlReceiptParse("cannot parse quantity as float")
-                                })?;
-                            Ok((quantity, is_weight))
+                    })
+                    .transpose()?;
+                let attr_unit_price = el
+                    .attr("data-unit-price")
+                    .map(|price| {
+                        parse_decimal(price).map_err(|_| {
+                            Error::HtmlReceiptParse("cannot parse unit price as decimal")
                         })
-                        .unwrap_or(Ok((1., false)))?;
+                    })
+                    .transpose()?;
 
-                    let item = ReceiptItem {
-                        unit_price,
-                        quantity,
-                        is_weight,
-                        name,
-                        barcode: format!("lidl-{}", id),
-                        discounts: vec![],
-                    };
-                    items.push(item);
+                // some countries leave out the quantity when it is 1, and some leave it out
+                // even when it is not, so it is read from the text as well
+                let quantity = attr_quantity
+                    .or(printed.quantity)
+                    .or_else(|| {
+                        let quantity = printed
+                            .total?
+                            .checked_div(attr_unit_price.or(printed.unit_price)?)?;
+                        quantity.fract().is_zero().then_some(quantity)
+                    })
+                    .unwrap_or(Decimal::ONE);
+                let unit_price = attr_unit_price
+                    .or(printed.unit_price)
+                    .or_else(|| printed.total?.checked_div(quantity))
+                    .map(|price| Money::new(price, currency.clone()))
+                    .ok_or(Error::HtmlReceiptParseMissingAttr("data-unit-price"))?;
+                // the total of the item is computed from these, so it must not overflow
+                if line_total(quantity, unit_price.amount).is_none() {
+                    Err(Error::HtmlReceiptParse(
+                        "quantity times unit price is too large",
+                    ))?;
                 }
-                "discount" => {
-                    let amount = element
-                        .text()
-                        .collect::<String>()
-                        .split_whitespace()
-                        .last()
-                        .and_then(|amount| amount.replace(',', ".").parse::<f64>().ok())
-                        .ok_or(Error::HtmlReceiptParse(
-                            "cannot parse discount amount as float",
-                        ))?
-                        .abs();
+                let is_weight = !quantity.fract().is_zero()
+                    || el
+                        .attr("data-art-quantity")
+                        .is_some_and(|quantity| quantity.contains(','));
+                let unit = printed.unit.unwrap_or(if is_weight {
+                    Unit::Kilogram
+                } else {
+                    Unit::Piece
+                });
 
-                    if let Some(item) = items.last_mut() {
-                        item.discounts.push(Discount { amount });
-                    } else {
-                        Err(Error::HtmlReceiptParse(
-                            "found discount but there are no products before it",
-                        ))?;
-                    }
+                let name = match el.attr("data-art-description") {
+                    Some(name) => name.to_string(),
+                    None if !printed.description.is_empty() => printed.description,
+                    None => Err(Error::HtmlReceiptParseMissingAttr("data-art-description"))?,
+                };
+                let kind = ItemKind::from_description(&name, unit_price.amount.is_sign_negative());
+
+                // deposits, bags and the like are not always printed with an article id,
+                // and they are never looked up by barcode anyway
+                let barcode = match get_attr(el, "data-art-id") {
+                    Ok(id) => format!("lidl-{}", id),
+                    Err(_) if kind != ItemKind::Product => String::new(),
+                    Err(error) => Err(error)?,
+                };
+
+                let tax = el
+                    .attr("data-tax-type")
+                    .map(str::to_string)
+                    .or(printed.tax_category)
+                    .map(|category| Tax {
+                        category,
+                        rate: None,
+                    });
+
+                let item = ReceiptItem {
+                    unit_price,
+                    quantity,
+                    unit,
+                    name,
+                    barcode,
+                    discounts: vec![],
+                    tax,
+                    kind,
+                    sources: None,
+                };
+                items.push(item);
+            }
+            "discount" => {
+                // e.g., "Lidl Plus-rabatt     -7,92"
+                let text = line.text.trim();
+                let (description, amount) =
+                    text.rsplit_once(char::is_whitespace).unwrap_or(("", text));
+                let amount = parse_decimal(amount)
+                    .map_err(|_| {
+                        Error::HtmlReceiptParse("cannot parse discount amount as decimal")
+                    })?
+                    .abs();
+                let description = Some(description.trim().to_string())
+                    .filter(|description| !description.is_empty());
+
+                if let Some(item) = items.last_mut() {
+                    item.discounts.push(Discount {
+                        amount: Money::new(amount, item.unit_price.currency.clone()),
+                        kind: description
+                            .as_deref()
+                            .map(DiscountKind::from_description)
+                            .unwrap_or_default(),
+                        description,
+                        promotion_id: el.attr("data-promotion-id").map(str::to_string),
+                    });
+                } else {
+                    Err(Error::HtmlReceiptParse(
+                        "found discount but there are no products before it",
+                    ))?;
                 }
-                _ => {}
             }
+            _ => {}
         }
     }
 
-    if let Some(currency) = currency {
-        Ok(ReceiptDetailed {
-            id,
-            items,
-            date,
-            currency,
-            store,
+    Ok(())
+}
+
+/// Amounts printed in the text of a product line, which are only used when the
+/// attributes of the line are missing
+#[derive(Debug, Default, PartialEq)]
+struct PrintedAmounts {
+    /// Text before the amounts, which is the name of the product in its first line
+    description: String,
+    quantity: Option<Decimal>,
+    unit: Option<Unit>,
+    unit_price: Option<Decimal>,
+    total: Option<Decimal>,
+    tax_category: Option<String>,
+}
+
+/// Read the amounts at the end of a product line, which are laid out differently in
+/// each country, e.g.:
+/// - "Grytbitar   79,90 x   2   159,80 C" (unit price, quantity, total and tax category)
+/// - "Paprika rot   1,59 A" (total, and the quantity is 1 or in the sub-line)
+/// - "  0,957 kg x 67,90  SEK/kg" (sub-line with the weight and the price per kg)
+/// - "  2 x 0,89" (sub-line with the quantity and the unit price)
+/// - "Masło extra   2 * 6,49 = 12,98 C" (quantity, unit price, total and tax category)
+fn parse_printed_amounts(text: &str) -> PrintedAmounts {
+    let mut words: Vec<&str> = text.split_whitespace().collect();
+    let mut printed = PrintedAmounts::default();
+
+    let number = |word: &str| parse_decimal(word).ok();
+    // units might be printed per price too (e.g., "SEK/kg")
+    let unit = |word: &str| Unit::from_symbol(word.rsplit('/').next().unwrap_or(word));
+    let is_times = |word: &str| word.eq_ignore_ascii_case("x") || word == "*";
+    // some countries print "2 * 6,49 = 12,98"
+    let is_equals = |word: &str| word == "=";
+
+    // the tax category is a single letter after the total (e.g., "C")
+    if let [.., total, category] = words[..] {
+        if category.len() == 1
+            && category.chars().all(|c| c.is_ascii_uppercase())
+            && number(total).is_some()
+        {
+            printed.tax_category = words.pop().map(str::to_string);
+        }
+    }
+
+    // amounts are at the end of the line, after the description
+    let amounts_start = words
+        .iter()
+        .rposition(|word| {
+            number(word).is_none() && unit(word).is_none() && !is_times(word) && !is_equals(word)
         })
+        .map_or(0, |position| position + 1);
+    printed.description = words[..amounts_start].join(" ");
+    let amounts: Vec<&str> = words[amounts_start..]
+        .iter()
+        .copied()
+        .filter(|word| !is_equals(word))
+        .collect();
+
+    let Some(times) = amounts.iter().position(|word| is_times(word)) else {
+        printed.total = amounts.iter().rev().find_map(|word| number(word));
+        return printed;
+    };
+
+    // e.g., "0,957 kg" or "79,90" before the "x"
+    let (left, left_unit) = match amounts[..times] {
+        [.., left, left_unit] if unit(left_unit).is_some() => (left, unit(left_unit)),
+        [.., left] => (left, None),
+        [] => return printed,
+    };
+    // e.g., "67,90 SEK/kg" or "2 159,80" after the "x"
+    let (right, right_unit, total) = match amounts[times + 1..] {
+        [right, right_unit, ref rest @ ..] if unit(right_unit).is_some() => {
+            (right, unit(right_unit), rest.first())
+        }
+        [right, ref rest @ ..] => (right, None, rest.first()),
+        [] => return printed,
+    };
+    let (Some(left_number), Some(right_number)) = (number(left), number(right)) else {
+        return printed;
+    };
+
+    // the quantity is the side with the unit (e.g., "0,957 kg x 67,90 SEK/kg"), or otherwise
+    // the side that is a whole number, since prices are printed with decimals
+    let has_decimals = |word: &str| word.contains([',', '.']);
+    let left_is_quantity =
+        left_unit.is_some() || right_unit.is_some() || !has_decimals(left) || has_decimals(right);
+    let (quantity, unit_price) = if left_is_quantity {
+        (left_number, right_number)
     } else {
-        Err(Error::HtmlReceiptParse(
-            "could not find currency in receipt",
-        ))
+        (right_number, left_number)
+    };
+
+    printed.total = total.and_then(|total| number(total));
+    // the description might end with amounts too (e.g., "Wasser 6 x 1,5 L   4,99 A"), so
+    // the amounts of a product line are only trusted if they add up to its total;
+    // sub-lines have no total, and are checked against their product instead.
+    // Numbers too large to be multiplied are not amounts either
+    let amounts_total = line_total(quantity, unit_price);
+    let is_amounts = amounts_total.is_some()
+        && (printed.description.is_empty() || printed.total == amounts_total);
+    if !is_amounts {
+        let description_end = words.len() - usize::from(printed.total.is_some());
+        printed.description = words[..description_end].join(" ");
+        return printed;
+    }
+
+    printed.quantity = Some(quantity);
+    printed.unit_price = Some(unit_price);
+    printed.unit = left_unit.or(right_unit);
+    printed
+}
+
+/// Total of a line, rounded like in the receipt, or `None` if it is too large to be
+/// represented
+fn line_total(quantity: Decimal, unit_price: Decimal) -> Option<Decimal> {
+    let total = quantity.checked_mul(unit_price)?;
+    Some(total.round_dp_with_strategy(
+        MINOR_UNIT_DECIMAL_PLACES,
+        RoundingStrategy::MidpointAwayFromZero,
+    ))
+}
+
+/// Complete a product with the amounts printed in its sub-line, if its line did not
+/// have them as attributes and they match the price of the product
+fn apply_subline(el: &Element, printed: &PrintedAmounts, item: &mut ReceiptItem) {
+    let (Some(quantity), Some(unit_price)) = (printed.quantity, printed.unit_price) else {
+        return;
+    };
+    let matches_item = line_total(quantity, unit_price) == Some(item.total_price().amount)
+        || unit_price == item.unit_price.amount;
+    let has_quantity_attr = el.attr("data-art-quantity").is_some();
+    let has_unit_price_attr = el.attr("data-unit-price").is_some();
+    let total = line_total(
+        if has_quantity_attr {
+            item.quantity
+        } else {
+            quantity
+        },
+        if has_unit_price_attr {
+            item.unit_price.amount
+        } else {
+            unit_price
+        },
+    );
+    if !matches_item || total.is_none() {
+        return;
+    }
+
+    if let Some(unit) = printed.unit {
+        item.unit = unit;
+    }
+    if !has_quantity_attr {
+        item.quantity = quantity;
+        if printed.unit.is_none() && !quantity.fract().is_zero() {
+            item.unit = Unit::Kilogram;
+        }
+    }
+    if !has_unit_price_attr {
+        item.unit_price.amount = unit_price;
+    }
+}
+
+/// Metadata from the header of the receipt
+struct Header {
+    address: Vec<String>,
+    country: Option<String>,
+    language: Option<String>,
+}
+
+/// Find the country and language of the till (e.g., `data-till-country="SE"`) and the
+/// lines printed above the products, which are the name and address of the store
+fn parse_header(dom: &Html) -> Header {
+    let selector = Selector::parse("span.header").unwrap();
+    let element = dom.select(&selector).next().map(|header| header.value());
+    let get_header_attr = |attr| {
+        element
+            .and_then(|element| element.attr(attr))
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    Header {
+        address: select_lines(dom, r#"span.header span[id^="header_line_"]"#)
+            .into_iter()
+            .map(|line| line.text.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect(),
+        country: get_header_attr("data-till-country"),
+        language: get_header_attr("data-receipt-language"),
+    }
+}
+
+fn parse_tax_summary(dom: &Html, currency: &Currency) -> Result<Vec<TaxSummary>, Error> {
+    let mut taxes: Vec<TaxSummary> = vec![];
+    for line in select_lines(dom, r#"span[id^="vat_info_line_"][data-tax-type]"#) {
+        let el = line.element;
+        let parse_amount = |attr| {
+            get_attr(el, attr).and_then(|amount| {
+                Money::parse(amount, currency.clone())
+                    .map_err(|_| Error::HtmlReceiptParse("cannot parse tax amount as decimal"))
+            })
+        };
+
+        taxes.push(TaxSummary {
+            category: get_attr(el, "data-tax-type")?.to_string(),
+            rate: get_attr(el, "data-tax-percentage").and_then(|rate| {
+                parse_decimal(rate)
+                    .map_err(|_| Error::HtmlReceiptParse("cannot parse tax rate as decimal"))
+            })?,
+            base_amount: parse_amount("data-tax-base-amount")?,
+            tax_amount: parse_amount("data-tax-amount")?,
+        });
     }
+
+    Ok(taxes)
+}
+
+/// Find the total to pay (e.g., "ATT BETALA   479,24"), which is the first line of the
+/// purchase summary ending with an amount that is not a payment
+fn parse_total(dom: &Html, currency: &Currency) -> Option<Money> {
+    select_lines(dom, r#"span[id^="purchase_summary_"]"#)
+        .into_iter()
+        .filter(|line| line.element.attr("data-tender-description").is_none())
+        .find_map(|line| {
+            let amount = line.text.split_whitespace().last()?;
+            Money::parse(amount, currency.clone()).ok()
+        })
+}
+
+/// Find the payments (e.g., "Kort   479,24"), which are the lines of the purchase summary
+/// that have a tender description
+fn parse_tenders(dom: &Html, currency: &Currency) -> Result<Vec<Tender>, Error> {
+    select_lines(
+        dom,
+        r#"span[id^="purchase_summary_"][data-tender-description]"#,
+    )
+    .into_iter()
+    .map(|line| {
+        let amount = line
+            .text
+            .split_whitespace()
+            .last()
+            .and_then(|amount| Money::parse(amount, currency.clone()).ok())
+            .ok_or(Error::HtmlReceiptParse(
+                "cannot parse tender amount as decimal",
+            ))?;
+
+        Ok(Tender {
+            method: get_attr(line.element, "data-tender-description")?.to_string(),
+            amount,
+        })
+    })
+    .collect()
+}
+
+/// Find the total discount (e.g., "Total rabatt   32,28"), which is not always present
+fn parse_total_discount(dom: &Html, currency: &Currency) -> Option<Money> {
+    const DISCOUNT_KEYWORDS: &[&str] = &["rabatt", "discount", "descuento", "sconto", "remise"];
+
+    select_lines(
+        dom,
+        r#"span[id^="purchase_tender_information_"], span[id^="vat_info_line_"]"#,
+    )
+    .into_iter()
+    .find_map(|line| {
+        let text = line.text.trim().to_lowercase();
+        if !text.starts_with("total")
+            || !DISCOUNT_KEYWORDS
+                .iter()
+                .any(|keyword| text.contains(keyword))
+        {
+            return None;
+        }
+
+        // the amount might be followed by the currency (e.g., "32,28 SEK")
+        text.split_whitespace()
+            .rev()
+            .find_map(|amount| Money::parse(amount, currency.clone()).ok())
+            .map(|amount| amount.abs())
+    })
+}
+
+/// A line of the receipt, which might be split across multiple spans
+struct Line<'a> {
+    /// Last span of the line
+    element: &'a Element,
+    /// Text of all the spans of the line
+    text: String,
+}
+
+fn select_lines<'a>(dom: &'a Html, selector: &str) -> Vec<Line<'a>> {
+    let selector = Selector::parse(selector).unwrap();
+
+    let mut lines: Vec<Line> = vec![];
+    for element in dom.select(&selector) {
+        let text = element.text().collect::<String>();
+        match lines.last_mut() {
+            // Lidl Germany has multiple spans with the same ID wrapping different parts of
+            // the same receipt line (probably a bug on their side), so join them together
+            Some(line) if line.element.id() == element.value().id() => {
+                line.element = element.value();
+                line.text.push_str(&text);
+            }
+            _ => lines.push(Line {
+                element: element.value(),
+                text,
+            }),
+        }
+    }
+
+    lines
 }
 
 fn get_attr<'a>(element: &'a Element, attr: &'static str) -> Result<&'a str, Error> {
@@ -126,17 +608,55 @@ fn get_attr<'a>(element: &'a Element, attr: &'static str) -> Result<&'a str, Err
 mod test {
     use anyhow::Result;
     use chrono::NaiveDate;
-    use ir::{Currency, Discount, ReceiptDetailed, ReceiptItem, Store};
+    use ir::{
+        money::parse_decimal, test_util, Currency, Discount, DiscountKind, ItemKind, Money,
+        ReceiptDetailed, ReceiptItem, Store, Tax, TaxSummary, Tender, Unit,
+    };
 
-    use super::parse_html_receipt;
+    use super::{
+        parse_html_receipt, parse_html_receipt_lenient, parse_printed_amounts, PrintedAmounts,
+    };
+
+    macro_rules! s {
+        ($s: expr) => {
+            ($s.to_owned())
+        };
+    }
+    macro_rules! d {
+        ($s: expr) => {
+            (parse_decimal($s).unwrap())
+        };
+    }
+    macro_rules! m {
+        ($currency: expr, $s: expr) => {
+            (Money::new(d!($s), $currency.clone()))
+        };
+    }
+    macro_rules! discount {
+        ($currency: expr, $amount: expr, $description: expr, $kind: ident) => {
+            (Discount {
+                amount: m!($currency, $amount),
+                description: Some(s!($description)),
+                promotion_id: Some(s!("CENSORED")),
+                kind: DiscountKind::$kind,
+            })
+        };
+    }
+    macro_rules! t {
+        ($category: expr, $rate: expr) => {
+            (Some(Tax {
+                category: s!($category),
+                rate: Some(d!($rate)),
+            }))
+        };
+    }
 
     #[test]
     fn test_parse_html_receipt() -> Result<()> {
-        macro_rules! s {
-            ($s: expr) => {
-                ($s.to_owned())
-            };
-        }
+        let currency = Currency {
+            id: s!("SEK"),
+            symbol: s!("kr"),
+        };
 
         let id = s!("test-id");
         let date = NaiveDate::from_ymd_opt(2024, 1, 1)
@@ -146,6 +666,8 @@ mod test {
         let store = Store {
             id: s!("STORE123"),
             name: s!("Example Store"),
+            address: vec![],
+            country: None,
         };
         let html = include_str!("../test/receipt.html");
         let receipt = parse_html_receipt(id.clone(), date, store.clone(), html)?;
@@ -154,142 +676,221 @@ mod test {
             id,
             items: vec![
                 ReceiptItem {
-                    unit_price: 79.9,
-                    quantity: 2.,
-                    is_weight: false,
+                    unit_price: m!(currency, "79.9"),
+                    quantity: d!("2"),
+                    unit: Unit::Piece,
                     name: s!("Grytbitar"),
                     barcode: s!("lidl-0051496"),
-                    discounts: vec![Discount { amount: 7.92 }],
+                    discounts: vec![discount!(currency, "7.92", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 67.9,
-                    quantity: 0.957,
-                    is_weight: true,
+                    unit_price: m!(currency, "67.9"),
+                    quantity: d!("0.957"),
+                    unit: Unit::Kilogram,
                     name: s!("Fläskfärs 20%"),
                     barcode: s!("lidl-7006839"),
-                    discounts: vec![Discount { amount: 3.22 }],
+                    discounts: vec![discount!(currency, "3.22", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 44.9,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "44.9"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Lufttorkad skinka"),
                     barcode: s!("lidl-6000753"),
-                    discounts: vec![Discount { amount: 2.23 }],
+                    discounts: vec![discount!(currency, "2.23", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 42.9,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "42.9"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Gouda i skivor"),
                     barcode: s!("lidl-6601728"),
-                    discounts: vec![Discount { amount: 2.13 }],
+                    discounts: vec![discount!(currency, "2.13", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 36.9,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "36.9"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Ägg frigående"),
                     barcode: s!("lidl-7005009"),
-                    discounts: vec![Discount { amount: 1.83 }],
+                    discounts: vec![discount!(currency, "1.83", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 29.9,
-                    quantity: 0.784,
-                    is_weight: true,
+                    unit_price: m!(currency, "29.9"),
+                    quantity: d!("0.784"),
+                    unit: Unit::Kilogram,
                     name: s!("Äpple Royal Gala"),
                     barcode: s!("lidl-0081329"),
-                    discounts: vec![Discount { amount: 1.16 }],
+                    discounts: vec![discount!(currency, "1.16", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 26.9,
-                    quantity: 0.814,
-                    is_weight: true,
+                    unit_price: m!(currency, "26.9"),
+                    quantity: d!("0.814"),
+                    unit: Unit::Kilogram,
                     name: s!("Banan, EKO Fairtrade"),
                     barcode: s!("lidl-0081510"),
-                    discounts: vec![Discount { amount: 1.09 }],
+                    discounts: vec![discount!(currency, "1.09", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 19.9,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "19.9"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Tvättsvamp disk"),
                     barcode: s!("lidl-0155075"),
-                    discounts: vec![Discount { amount: 0.99 }],
+                    discounts: vec![discount!(currency, "0.99", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("D", "25"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 19.9,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "19.9"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Friséemix"),
                     barcode: s!("lidl-7006714"),
-                    discounts: vec![Discount { amount: 0.99 }],
+                    discounts: vec![discount!(currency, "0.99", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 18.5,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "18.5"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Arla Mellanmjölk"),
                     barcode: s!("lidl-7003351"),
-                    discounts: vec![Discount { amount: 0.92 }],
+                    discounts: vec![discount!(currency, "0.92", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 11.9,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "11.9"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Zucchini styck"),
                     barcode: s!("lidl-0082346"),
-                    discounts: vec![Discount { amount: 0.58 }],
+                    discounts: vec![discount!(currency, "0.58", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 29.9,
-                    quantity: 0.472,
-                    is_weight: true,
+                    unit_price: m!(currency, "29.9"),
+                    quantity: d!("0.472"),
+                    unit: Unit::Kilogram,
                     name: s!("Sötpotatis, lösvikt"),
                     barcode: s!("lidl-0080755"),
-                    discounts: vec![Discount { amount: 2.83 }, Discount { amount: 0.56 }],
+                    discounts: vec![
+                        discount!(currency, "2.83", "Lidl Plus-rabatt", Loyalty),
+                        discount!(currency, "0.56", "Lidl Plus-rabatt", Loyalty),
+                    ],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 8.9,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "8.9"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Fruktyoghurt jord."),
                     barcode: s!("lidl-0001149"),
-                    discounts: vec![Discount { amount: 0.44 }],
+                    discounts: vec![discount!(currency, "0.44", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 8.9,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "8.9"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Fruktyoghurt mango"),
                     barcode: s!("lidl-0001149"),
-                    discounts: vec![Discount { amount: 0.44 }],
+                    discounts: vec![discount!(currency, "0.44", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 17.9,
-                    quantity: 0.556,
-                    is_weight: true,
+                    unit_price: m!(currency, "17.9"),
+                    quantity: d!("0.556"),
+                    unit: Unit::Kilogram,
                     name: s!("Morötter lösvikt"),
                     barcode: s!("lidl-0082755"),
-                    discounts: vec![Discount { amount: 4.45 }, Discount { amount: 0.27 }],
+                    discounts: vec![
+                        discount!(currency, "4.45", "Lidl Plus Erbjudand", Coupon),
+                        discount!(currency, "0.27", "Lidl Plus-rabatt", Loyalty),
+                    ],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 12.9,
-                    quantity: 0.36,
-                    is_weight: true,
+                    unit_price: m!(currency, "12.9"),
+                    quantity: d!("0.36"),
+                    unit: Unit::Kilogram,
                     name: s!("Lök, gul lösvikt"),
                     barcode: s!("lidl-0083325"),
-                    discounts: vec![Discount { amount: 0.23 }],
+                    discounts: vec![discount!(currency, "0.23", "Lidl Plus-rabatt", Loyalty)],
+                    tax: t!("C", "12"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
             ],
             date,
-            currency: Currency {
-                id: s!("SEK"),
-                symbol: s!("kr"),
+            currency: currency.clone(),
+            store: Store {
+                address: vec![s!("CENSORED"), s!("CENSORED")],
+                country: Some(s!("SE")),
+                ..store
             },
-            store,
+            taxes: vec![
+                TaxSummary {
+                    category: s!("C"),
+                    rate: d!("12"),
+                    base_amount: m!(currency, "460.33"),
+                    tax_amount: m!(currency, "49.32"),
+                },
+                TaxSummary {
+                    category: s!("D"),
+                    rate: d!("25"),
+                    base_amount: m!(currency, "18.91"),
+                    tax_amount: m!(currency, "3.78"),
+                },
+            ],
+            total: Some(m!(currency, "479.24")),
+            total_discount: Some(m!(currency, "32.28")),
+            language: Some(s!("sv")),
+            tenders: vec![Tender {
+                method: s!("Kort"),
+                amount: m!(currency, "479.24"),
+            }],
+            sources: None,
         };
 
+        assert!(receipt.reconcile()?.is_balanced());
+
         assert_eq!(expected, receipt);
 
         Ok(())
@@ -297,11 +898,10 @@ mod test {
 
     #[test]
     fn test_parse_html_receipt_duplicate_spans() -> Result<()> {
-        macro_rules! s {
-            ($s: expr) => {
-                ($s.to_owned())
-            };
-        }
+        let currency = Currency {
+            id: s!("EUR"),
+            symbol: s!("€"),
+        };
 
         let id = s!("test-id");
         let date = NaiveDate::from_ymd_opt(2024, 1, 1)
@@ -311,6 +911,8 @@ mod test {
         let store = Store {
             id: s!("STORE123"),
             name: s!("Example Store"),
+            address: vec![],
+            country: None,
         };
         let html = include_str!("../test/receipt_duplicate_spans.html");
         let receipt = parse_html_receipt(id.clone(), date, store.clone(), html)?;
@@ -319,72 +921,352 @@ mod test {
             id,
             items: vec![
                 ReceiptItem {
-                    unit_price: 2.99,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "2.99"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Cherrystrauchtomaten"),
                     barcode: s!("lidl-0082388"),
-                    discounts: vec![Discount { amount: 1.00 }],
+                    discounts: vec![discount!(currency, "1.00", "Lidl Plus Rabatt", Loyalty)],
+                    tax: t!("A", "7"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 1.59,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "1.59"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Paprika rot"),
                     barcode: s!("lidl-0082620"),
-                    discounts: vec![Discount { amount: 0.48 }],
+                    discounts: vec![discount!(currency, "0.48", "Lidl Plus Rabatt", Loyalty)],
+                    tax: t!("A", "7"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 1.79,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "1.79"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("K.champignons"),
                     barcode: s!("lidl-0083017"),
                     discounts: vec![],
+                    tax: t!("A", "7"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 1.79,
-                    quantity: 1.,
-                    is_weight: false,
+                    unit_price: m!(currency, "1.79"),
+                    quantity: d!("1"),
+                    unit: Unit::Piece,
                     name: s!("Vegane Spätzle"),
                     barcode: s!("lidl-7711334"),
                     discounts: vec![],
+                    tax: t!("A", "7"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 0.89,
-                    quantity: 2.,
-                    is_weight: false,
+                    unit_price: m!(currency, "0.89"),
+                    quantity: d!("2"),
+                    unit: Unit::Piece,
                     name: s!("Veg. Reibegenuss"),
                     barcode: s!("lidl-6612316"),
                     discounts: vec![],
+                    tax: t!("A", "7"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 2.19,
-                    quantity: 2.0,
-                    is_weight: false,
+                    unit_price: m!(currency, "2.19"),
+                    quantity: d!("2.0"),
+                    unit: Unit::Piece,
                     name: s!("Bioland Tofu geräu."),
                     barcode: s!("lidl-0175011"),
                     discounts: vec![],
+                    tax: t!("A", "7"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
                 ReceiptItem {
-                    unit_price: 0.95,
-                    quantity: 1.0,
-                    is_weight: false,
+                    unit_price: m!(currency, "0.95"),
+                    quantity: d!("1.0"),
+                    unit: Unit::Piece,
                     name: s!("Sojajoghurt Natur"),
                     barcode: s!("lidl-0165195"),
                     discounts: vec![],
+                    tax: t!("A", "7"),
+                    kind: ItemKind::Product,
+                    sources: None,
                 },
             ],
             date,
-            currency: Currency {
-                id: s!("EUR"),
-                symbol: s!("€"),
+            currency: currency.clone(),
+            store: Store {
+                address: vec![s!("CENSORED"), s!("CENSORED")],
+                country: Some(s!("DE")),
+                ..store
             },
-            store,
+            taxes: vec![TaxSummary {
+                category: s!("A"),
+                rate: d!("7"),
+                base_amount: m!(currency, "28.92"),
+                tax_amount: m!(currency, "1.89"),
+            }],
+            total: Some(m!(currency, "28.92")),
+            total_discount: None,
+            language: Some(s!("de")),
+            tenders: vec![Tender {
+                method: s!("Kreditkarte"),
+                amount: m!(currency, "28.92"),
+            }],
+            sources: None,
         };
 
         assert_eq!(expected, receipt);
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_html_receipt_deposits() -> Result<()> {
+        let html = r#"<span class="purchase_list">
+<span id="purchase_list_line_1" class="currency" data-currency="€">EUR</span>
+<span id="purchase_list_line_2" class="article" data-art-id="0080390" data-unit-price="0,19" data-tax-type="B" data-art-description="Mineralwasser">Mineralwasser         0,19 B</span>
+<span id="purchase_list_line_3" class="article" data-unit-price="0,25" data-tax-type="B" data-art-description="Pfand 0,25 EUR">Pfand 0,25 EUR        0,25 B</span>
+<span id="purchase_list_line_4" class="article" data-art-id="0000009" data-unit-price="-0,75" data-tax-type="B" data-art-description="Leergut">Leergut              -0,75 B</span>
+<span id="purchase_list_line_5" class="article" data-unit-price="0,20" data-tax-type="B" data-art-description="Tragetasche">Tragetasche           0,20 B</span>
+</span>"#;
+        let ReceiptDetailed {
+            id, date, store, ..
+        } = test_util::receipt(vec![]);
+        let receipt = parse_html_receipt(id, date, store, html)?;
+
+        let items: Vec<_> = receipt
+            .items
+            .iter()
+            .map(|item| (item.kind, item.barcode.as_str(), item.unit_price.amount))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                (ItemKind::Product, "lidl-0080390", parse_decimal("0.19")?),
+                (ItemKind::Deposit, "", parse_decimal("0.25")?),
+                (
+                    ItemKind::DepositReturn,
+                    "lidl-0000009",
+                    parse_decimal("-0.75")?
+                ),
+                (ItemKind::Bag, "", parse_decimal("0.20")?),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_html_receipt_lenient() -> Result<()> {
+        let html = r#"<span class="purchase_list">
+<span id="purchase_list_line_1" class="currency" data-currency="€">EUR</span>
+<span id="purchase_list_line_2" class="discount" data-promotion-id="1">   Rabatt   -0,10</span>
+<span id="purchase_list_line_3" class="article" data-art-id="0082388" data-tax-type="A" data-art-description="Tomaten">Tomaten</span>
+<span id="purchase_list_line_4" class="discount" data-promotion-id="2">   Lidl Plus Rabatt   -1,00</span>
+<span id="purchase_list_line_5" class="article" data-art-id="0082620" data-unit-price="1,59" data-tax-type="A" data-art-description="Paprika rot">Paprika rot      1,59 A</span>
+<span id="purchase_list_line_6" class="discount" data-promotion-id="3">   Lidl Plus Rabatt   -0,4x</span>
+<span id="purchase_list_line_7" class="article" data-art-id="0165195" data-unit-price="0,95" data-tax-type="A" data-art-description="Sojajoghurt">Sojajoghurt      0,95 A</span>
+<span id="purchase_list_line_8" class="article" data-art-id="0000001" data-art-quantity="99999999999999999999" data-unit-price="99999999999999999999" data-art-description="Overflow">Overflow      1,00</span>
+</span>"#;
+        let ReceiptDetailed {
+            id, date, store, ..
+        } = test_util::receipt(vec![]);
+
+        let parsed = parse_html_receipt_lenient(id.clone(), date, store.clone(), html)?;
+        let names: Vec<_> = parsed
+            .receipt
+            .items
+            .iter()
+            .map(|item| (item.name.as_str(), item.discounts.len()))
+            .collect();
+        assert_eq!(names, vec![("Paprika rot", 0), ("Sojajoghurt", 0)]);
+
+        let warnings: Vec<_> = parsed
+            .warnings
+            .iter()
+            .map(|warning| (warning.line_id.as_str(), warning.position))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                ("purchase_list_line_2", 0),
+                ("purchase_list_line_3", 0),
+                ("purchase_list_line_4", 0),
+                ("purchase_list_line_6", 1),
+                ("purchase_list_line_8", 2),
+            ]
+        );
+        assert_eq!(parsed.warnings[1].text, "Tomaten");
+
+        // the strict parser fails on the first of these lines
+        assert!(parse_html_receipt(id, date, store, html).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_html_receipt_units() -> Result<()> {
+        let html = r#"<span class="purchase_list">
+<span id="purchase_list_line_1" class="currency" data-currency="$">USD</span>
+<span id="purchase_list_line_2" class="article" data-art-id="0012345" data-art-quantity="1,52" data-unit-price="0,59" data-art-description="Bananas">Bananas              0,90</span>
+<span id="purchase_list_line_3" class="article" data-art-id="0012345" data-art-quantity="1,52" data-unit-price="0,59" data-art-description="Bananas">  1,52 lb x 0,59  USD/lb</span>
+<span id="purchase_list_line_4" class="article" data-art-id="0067890" data-art-quantity="2" data-unit-price="1,00" data-art-description="Lemons">Lemons         2,00</span>
+</span>"#;
+        let ReceiptDetailed {
+            id, date, store, ..
+        } = test_util::receipt(vec![]);
+        let receipt = parse_html_receipt(id, date, store, html)?;
+
+        let units: Vec<_> = receipt.items.iter().map(|item| item.unit).collect();
+        assert_eq!(units, vec![Unit::Pound, Unit::Piece]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_printed_amounts() -> Result<()> {
+        let d = |value: &str| parse_decimal(value).ok();
+
+        assert_eq!(
+            parse_printed_amounts("Grytbitar           79,90 x   2   159,80 C"),
+            PrintedAmounts {
+                description: "Grytbitar".to_owned(),
+                quantity: d("2"),
+                unit: None,
+                unit_price: d("79.90"),
+                total: d("159.80"),
+                tax_category: Some("C".to_owned()),
+            }
+        );
+        assert_eq!(
+            parse_printed_amounts("  0,957 kg x 67,90  SEK/kg"),
+            PrintedAmounts {
+                quantity: d("0.957"),
+                unit: Some(Unit::Kilogram),
+                unit_price: d("67.90"),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            parse_printed_amounts("  2 X 0,89"),
+            PrintedAmounts {
+                quantity: d("2"),
+                unit_price: d("0.89"),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            parse_printed_amounts("Fläskfärs 20%                      64,98 C"),
+            PrintedAmounts {
+                description: "Fläskfärs 20%".to_owned(),
+                total: d("64.98"),
+                tax_category: Some("C".to_owned()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            parse_printed_amounts("Masło extra      2 * 6,49 = 12,98 C"),
+            PrintedAmounts {
+                description: "Masło extra".to_owned(),
+                quantity: d("2"),
+                unit_price: d("6.49"),
+                total: d("12.98"),
+                tax_category: Some("C".to_owned()),
+                ..Default::default()
+            }
+        );
+        // the size of the product is not its quantity, since it does not add up to the total
+        assert_eq!(
+            parse_printed_amounts("Wasser 6 x 1,5 L               4,99 A"),
+            PrintedAmounts {
+                description: "Wasser 6 x 1,5 L".to_owned(),
+                total: d("4.99"),
+                tax_category: Some("A".to_owned()),
+                ..Default::default()
+            }
+        );
+        // numbers too large to be multiplied are not amounts
+        assert_eq!(
+            parse_printed_amounts("  99999999999999999999 x 99999999999999999999"),
+            PrintedAmounts {
+                description: "99999999999999999999 x 99999999999999999999".to_owned(),
+                ..Default::default()
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Receipts of other countries, whose lines are missing some attributes,
+    /// so that their amounts must be read from the text
+    #[test]
+    fn test_parse_html_receipt_layouts() -> Result<()> {
+        let receipts = [
+            (include_str!("../test/receipt_es.html"), "ES"),
+            (include_str!("../test/receipt_pt.html"), "PT"),
+            (include_str!("../test/receipt_pl.html"), "PL"),
+        ];
+        let expected_items = [
+            vec![
+                ("Leche entera", "2", Unit::Piece, "1.25"),
+                ("Tomate pera", "0.512", Unit::Kilogram, "2.49"),
+                ("Aceite de oliva", "1", Unit::Piece, "6.95"),
+            ],
+            vec![
+                ("Iogurte natural", "2", Unit::Piece, "0.89"),
+                ("Bananas", "1.100", Unit::Kilogram, "1.30"),
+                ("Vinho tinto", "2", Unit::Piece, "3.99"),
+            ],
+            vec![
+                ("Masło extra", "2", Unit::Piece, "6.49"),
+                ("Chleb żytni", "1", Unit::Piece, "4.99"),
+            ],
+        ];
+
+        for ((html, country), expected_items) in receipts.into_iter().zip(expected_items) {
+            let ReceiptDetailed {
+                id, date, store, ..
+            } = test_util::receipt(vec![]);
+            let receipt = parse_html_receipt(id, date, store, html)?;
+            assert_eq!(receipt.store.country.as_deref(), Some(country));
+
+            let items: Vec<_> = receipt
+                .items
+                .iter()
+                .map(|item| {
+                    (
+                        item.name.as_str(),
+                        item.quantity,
+                        item.unit,
+                        item.unit_price.amount,
+                    )
+                })
+                .collect();
+            let expected_items = expected_items
+                .into_iter()
+                .map(|(name, quantity, unit, unit_price)| {
+                    Ok((
+                        name,
+                        parse_decimal(quantity)?,
+                        unit,
+                        parse_decimal(unit_price)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(items, expected_items, "items of the {} receipt", country);
+            assert!(
+                receipt.reconcile()?.is_balanced(),
+                "total of the {} receipt",
+                country
+            );
+        }
+
+        Ok(())
+    }
 }
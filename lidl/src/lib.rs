@@ -1,22 +1,32 @@
 use std::borrow::Cow;
+use std::cell::Cell;
 use std::collections::HashMap;
 
 use anyhow::Result;
-use ir::{ReceiptSummary, StoreApi};
-use oauth2::basic::BasicClient;
+use chrono::{DateTime, Utc};
+use ir::{ReceiptPage, ReceiptSummary, StoreApi};
+use oauth2::basic::{BasicClient, BasicErrorResponse, BasicErrorResponseType};
 use oauth2::{
     AuthUrl, AuthorizationCode, ClientId, CsrfToken, PkceCodeChallenge, PkceCodeVerifier,
-    RedirectUrl, RefreshToken, Scope, TokenResponse, TokenType, TokenUrl,
+    RedirectUrl, RefreshToken, RequestTokenError, Scope, TokenResponse, TokenType, TokenUrl,
 };
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, RequestBuilder, Response};
 use reqwest::header::{HeaderMap, ACCEPT_LANGUAGE, AUTHORIZATION};
-use reqwest::Url;
+use reqwest::{StatusCode, Url};
+use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use structs::{Country, Language, ReceiptDetailed, ReceiptsPage, UnifiedReceiptDetailed};
 
-use crate::error::Error;
+pub use crate::archive::ResponseArchive;
+pub use crate::retry::RetryPolicy;
+use crate::{archive::ApiVersion, error::Error};
 
+pub mod anonymize;
+mod archive;
 pub mod error;
-mod html_receipt;
+pub mod html_receipt;
+mod merge;
+mod retry;
 pub mod structs;
 
 const APPGATEWAY_ENDPOINT: &str = "https://appgateway.lidlplus.com";
@@ -31,14 +41,50 @@ const OAUTH_AUTHORIZE_PATH: &str = "connect/authorize";
 const OAUTH_TOKEN_PATH: &str = "connect/token";
 const OAUTH_AUTHORIZATION_HEADER: &str = "Basic TGlkbFBsdXNOYXRpdmVDbGllbnQ6c2VjcmV0"; // LidlPlusNativeClient:secret in base64
 
+/// Base URLs of the Lidl Plus services
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Endpoints {
+    pub appgateway: String,
+    pub accounts: String,
+    pub tickets: String,
+}
+
+impl Endpoints {
+    /// Serve all services from the same base URL (e.g., a local server in tests)
+    pub fn with_base_url(base_url: &str) -> Self {
+        let base_url = base_url.trim_end_matches('/');
+        Self {
+            appgateway: base_url.to_string(),
+            accounts: base_url.to_string(),
+            tickets: base_url.to_string(),
+        }
+    }
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            appgateway: APPGATEWAY_ENDPOINT.to_string(),
+            accounts: ACCOUNTS_ENDPOINT.to_string(),
+            tickets: TICKETS_ENDPOINT.to_string(),
+        }
+    }
+}
+
 pub struct LidlApi {
+    access_token: String,
+    access_token_expiry: Option<DateTime<Utc>>,
     refresh_token: String,
     country_code: String,
+    endpoints: Endpoints,
+    retry_policy: RetryPolicy,
+    archive: Option<ResponseArchive>,
     client: Client,
 }
 
 impl LidlApi {
     fn from_token_response<TT>(
+        endpoints: Endpoints,
         country_code: String,
         language_code: String,
         token_response: &impl TokenResponse<TT>,
@@ -46,23 +92,62 @@ impl LidlApi {
     where
         TT: TokenType,
     {
-        let access_token = token_response.access_token().secret();
+        let refresh_token = token_response
+            .refresh_token()
+            .ok_or(Error::OAuthMissingRefreshToken)?
+            .secret()
+            .clone();
+        let access_token_expiry = token_response
+            .expires_in()
+            .and_then(|expires_in| chrono::Duration::from_std(expires_in).ok())
+            .map(|expires_in| Utc::now() + expires_in);
+
+        Self::from_access_token(
+            endpoints,
+            country_code,
+            language_code,
+            token_response.access_token().secret().clone(),
+            access_token_expiry,
+            refresh_token,
+        )
+    }
+
+    /// Use an access token obtained before (e.g., in a previous run), without asking
+    /// Lidl for a new one; it is up to the caller to check that it has not expired
+    pub fn from_access_token(
+        endpoints: Endpoints,
+        country_code: String,
+        language_code: String,
+        access_token: String,
+        access_token_expiry: Option<DateTime<Utc>>,
+        refresh_token: String,
+    ) -> Result<Self> {
         let mut headers = HeaderMap::new();
         headers.append(AUTHORIZATION, format!("Bearer {}", access_token).parse()?);
         headers.append(ACCEPT_LANGUAGE, language_code.parse()?);
         Ok(Self {
-            refresh_token: token_response
-                .refresh_token()
-                .ok_or(Error::OAuthMissingRefreshToken)?
-                .secret()
-                .clone(),
+            access_token,
+            access_token_expiry,
+            refresh_token,
             country_code,
+            endpoints,
+            retry_policy: RetryPolicy::default(),
+            archive: None,
             client: reqwest::blocking::Client::builder()
                 .default_headers(headers)
                 .build()?,
         })
     }
 
+    pub fn get_access_token(&self) -> String {
+        self.access_token.clone()
+    }
+
+    /// When the access token expires, if Lidl said so
+    pub fn get_access_token_expiry(&self) -> Option<DateTime<Utc>> {
+        self.access_token_expiry
+    }
+
     pub fn get_refresh_token(&self) -> String {
         self.refresh_token.clone()
     }
@@ -70,68 +155,189 @@ impl LidlApi {
     pub fn get_country_code(&self) -> String {
         self.country_code.clone()
     }
-}
 
-impl StoreApi for LidlApi {
-    fn get_available_receipts(&self) -> Result<Vec<ReceiptSummary>> {
-        let receipts_page: ReceiptsPage = self
-            .client
-            .get(format!(
-                "{}/api/v2/{}/tickets",
-                TICKETS_ENDPOINT, self.country_code,
+    /// Get the raw response of a receipt from a version of the tickets API,
+    /// archiving it if there is an archive
+    fn get_receipt_response(
+        &self,
+        receipt_summary: &ReceiptSummary,
+        version: ApiVersion,
+    ) -> Result<String> {
+        let api_version = match version {
+            ApiVersion::V2 => "v2",
+            ApiVersion::V3 => "v3",
+        };
+        let body = read_body(send(&self.retry_policy, || {
+            self.client.get(format!(
+                "{}/api/{}/{}/tickets/{}",
+                self.endpoints.tickets, api_version, self.country_code, receipt_summary.id
             ))
-            .query(&[
-                ("pageNumber", "1"),
-                ("onlyFavorite", "false"),
-                ("itemId", ""),
-            ])
-            .send()?
-            .json()?;
+        })?)?;
 
-        Ok(receipts_page.receipts.into_iter().map(Into::into).collect())
+        if let Some(archive) = &self.archive {
+            archive.save_receipt_response(receipt_summary, version, &body)?;
+        }
+        Ok(body)
     }
 
-    fn get_specific_receipt(&self, receipt: &ReceiptSummary) -> Result<ir::ReceiptDetailed> {
-        let receipt_v2_response = self
-            .client
-            .get(format!(
-                "{}/api/v2/{}/tickets/{}",
-                TICKETS_ENDPOINT, self.country_code, receipt.id
-            ))
-            .send()?;
+    /// Change how requests that fail with a transient error are retried
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
 
-        if receipt_v2_response.status().is_success() {
-            let receipt: ReceiptDetailed<f64> = receipt_v2_response
-                .json::<ReceiptDetailed<String>>()?
-                .try_into()?;
+    /// Save the raw responses of the tickets API to an archive, so that the receipts
+    /// can be parsed again later without network
+    pub fn with_archive(mut self, archive: ResponseArchive) -> Self {
+        self.archive = Some(archive);
+        self
+    }
+}
 
-            Ok(receipt.into())
-        } else {
-            let receipt: UnifiedReceiptDetailed = self
-                .client
+impl StoreApi for LidlApi {
+    fn get_receipts_page(&self, page: u32) -> Result<ReceiptPage> {
+        let body = read_body(send(&self.retry_policy, || {
+            self.client
                 .get(format!(
-                    "{}/api/v3/{}/tickets/{}",
-                    TICKETS_ENDPOINT, self.country_code, receipt.id
+                    "{}/api/v2/{}/tickets",
+                    self.endpoints.tickets, self.country_code,
                 ))
-                .send()?
-                .json()?;
-
-            Ok(html_receipt::parse_html_receipt(
-                receipt.id,
-                receipt.date,
-                receipt.store.into(),
-                &receipt.html_printed_receipt,
-            )?)
+                .query(&[
+                    ("pageNumber", page.to_string().as_str()),
+                    ("onlyFavorite", "false"),
+                    ("itemId", ""),
+                ])
+        })?)?;
+        if let Some(archive) = &self.archive {
+            archive.save_receipts_page(page, &body)?;
+        }
+
+        Ok(parse_json::<ReceiptsPage>(&body)?.into())
+    }
+
+    /// Get a receipt from the v2 API, or from the HTML of the v3 API if the v2 API does not
+    /// have it; see [`LidlApi::get_specific_receipt_with_html`] to complete it with the HTML
+    fn get_specific_receipt(
+        &self,
+        receipt_summary: &ReceiptSummary,
+    ) -> Result<ir::ReceiptDetailed> {
+        self.get_receipt(receipt_summary, false)
+    }
+}
+
+impl LidlApi {
+    /// Like [`StoreApi::get_specific_receipt`], but the lines of the receipt of the v2 API are
+    /// completed with the HTML receipt of the v3 API (e.g., with tax categories and
+    /// promotion ids), which takes another request
+    pub fn get_specific_receipt_with_html(
+        &self,
+        receipt_summary: &ReceiptSummary,
+    ) -> Result<ir::ReceiptDetailed> {
+        self.get_receipt(receipt_summary, true)
+    }
+
+    fn get_receipt(
+        &self,
+        receipt_summary: &ReceiptSummary,
+        with_html: bool,
+    ) -> Result<ir::ReceiptDetailed> {
+        let v2 = match self.get_receipt_response(receipt_summary, ApiVersion::V2) {
+            Ok(body) => Some(body),
+            // receipts of some countries are only available through the v3 API, and the v2
+            // API might be failing while the v3 one works
+            Err(error)
+                if error
+                    .downcast_ref::<Error>()
+                    .is_some_and(Error::is_unsuccessful_response) =>
+            {
+                None
+            }
+            Err(error) => return Err(error),
+        };
+        if v2.is_some() && !with_html {
+            return parse_receipt(v2.as_deref(), None);
         }
+        let v3 = match self.get_receipt_response(receipt_summary, ApiVersion::V3) {
+            Ok(body) => Some(body),
+            // the HTML receipt is only used to complete the v2 one, so it is fine
+            // if it cannot be fetched
+            Err(_) if v2.is_some() => None,
+            Err(error) => return Err(error),
+        };
+
+        parse_receipt(v2.as_deref(), v3.as_deref())
     }
 }
 
-pub fn get_countries() -> Result<Vec<Country>> {
-    Ok(reqwest::blocking::get(format!(
-        "{}/{}",
-        APPGATEWAY_ENDPOINT, "configurationapp/v3/countries"
-    ))?
-    .json()?)
+/// Parse the raw responses of a receipt from the v2 and v3 APIs, merging them if there
+/// are both; at least one of them must be given
+fn parse_receipt(v2: Option<&str>, v3: Option<&str>) -> Result<ir::ReceiptDetailed> {
+    let html_receipt = v3.map(parse_html_receipt_response);
+    match (v2, html_receipt) {
+        (Some(v2), html_receipt) => {
+            let receipt: ReceiptDetailed<Decimal> =
+                parse_json::<ReceiptDetailed<String>>(v2)?.try_into()?;
+
+            // the HTML receipt is only used to complete the v2 one, so it is fine
+            // if it cannot be parsed
+            Ok(match html_receipt {
+                Some(Ok(html_receipt)) => merge::merge_receipts(receipt.into(), html_receipt),
+                _ => receipt.into(),
+            })
+        }
+        (None, Some(html_receipt)) => html_receipt,
+        (None, None) => Err(Error::UnexpectedPayload("no receipt in the responses".into()).into()),
+    }
+}
+
+/// Parse a receipt from the v3 API, which only has the HTML of the printed receipt
+fn parse_html_receipt_response(body: &str) -> Result<ir::ReceiptDetailed> {
+    let receipt: UnifiedReceiptDetailed = parse_json(body)?;
+
+    Ok(html_receipt::parse_html_receipt(
+        receipt.id,
+        receipt.date,
+        receipt.store.into(),
+        &receipt.html_printed_receipt,
+    )?)
+}
+
+pub fn get_countries(endpoints: &Endpoints, retry_policy: &RetryPolicy) -> Result<Vec<Country>> {
+    let client = Client::new();
+    let body = read_body(send(retry_policy, || {
+        client.get(format!(
+            "{}/{}",
+            endpoints.appgateway, "configurationapp/v3/countries"
+        ))
+    })?)?;
+    Ok(parse_json(&body)?)
+}
+
+/// Send a request, retrying it while it fails with a transient error,
+/// and turn unsuccessful responses into errors
+fn send(
+    retry_policy: &RetryPolicy,
+    request: impl Fn() -> RequestBuilder,
+) -> Result<Response, Error> {
+    retry_policy.run(|| {
+        let response = request()
+            .send()
+            .map_err(|error| Error::Connection(error.to_string()))?;
+        match Error::from_status(response.status()) {
+            Some(error) => Err(error),
+            None => Ok(response),
+        }
+    })
+}
+
+fn read_body(response: Response) -> Result<String, Error> {
+    response
+        .text()
+        .map_err(|error| Error::Connection(error.to_string()))
+}
+
+fn parse_json<T: DeserializeOwned>(body: &str) -> Result<T, Error> {
+    serde_json::from_str(body).map_err(|error| Error::UnexpectedPayload(error.to_string()))
 }
 
 pub struct OAuthFlow {
@@ -141,23 +347,28 @@ pub struct OAuthFlow {
     pkce_verifier: PkceCodeVerifier,
     country_code: String,
     language_code: String,
+    endpoints: Endpoints,
 }
 
 impl OAuthFlow {
-    fn init_client() -> Result<BasicClient> {
+    fn init_client(endpoints: &Endpoints) -> Result<BasicClient> {
         Ok(BasicClient::new(
             ClientId::new(OAUTH_CLIENT_ID.to_string()),
             None,
-            AuthUrl::new(format!("{}/{}", ACCOUNTS_ENDPOINT, OAUTH_AUTHORIZE_PATH))?,
+            AuthUrl::new(format!("{}/{}", endpoints.accounts, OAUTH_AUTHORIZE_PATH))?,
             Some(TokenUrl::new(format!(
                 "{}/{}",
-                ACCOUNTS_ENDPOINT, OAUTH_TOKEN_PATH
+                endpoints.accounts, OAUTH_TOKEN_PATH
             ))?),
         )
         .set_redirect_uri(RedirectUrl::new(OAUTH_REDIRECT_URL.to_string())?))
     }
-    pub fn init(country: &Country, language: &Language) -> Result<OAuthFlow> {
-        let client = Self::init_client()?;
+    pub fn init(
+        endpoints: &Endpoints,
+        country: &Country,
+        language: &Language,
+    ) -> Result<OAuthFlow> {
+        let client = Self::init_client(endpoints)?;
 
         let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
 
@@ -182,6 +393,7 @@ impl OAuthFlow {
             pkce_verifier,
             country_code: country.id.clone(),
             language_code: language.id.clone(),
+            endpoints: endpoints.clone(),
         })
     }
 
@@ -205,27 +417,47 @@ impl OAuthFlow {
             return Err(Error::OAuthCsrfMismatch.into());
         }
 
+        let status = Cell::new(None);
         let token_response = self
             .client
             .exchange_code(AuthorizationCode::new(code.to_string()))
             .set_pkce_verifier(self.pkce_verifier)
-            .request(Self::http_client)?;
-
-        LidlApi::from_token_response(self.country_code, self.language_code, &token_response)
+            .request(|request| Self::http_client_with_status(request, &status))
+            .map_err(|error| Self::token_error(error, status.get()))?;
+
+        LidlApi::from_token_response(
+            self.endpoints,
+            self.country_code,
+            self.language_code,
+            &token_response,
+        )
     }
 
     pub fn get_token_from_refresh_token(
+        endpoints: &Endpoints,
         country_code: String,
         language_code: String,
         refresh_token: String,
+        retry_policy: &RetryPolicy,
     ) -> Result<LidlApi> {
-        let client = Self::init_client()?;
-
-        let token_response = client
-            .exchange_refresh_token(&RefreshToken::new(refresh_token))
-            .request(Self::http_client)?;
-
-        LidlApi::from_token_response(country_code, language_code, &token_response)
+        let client = Self::init_client(endpoints)?;
+
+        let refresh_token = RefreshToken::new(refresh_token);
+        let token_response = retry_policy.run(|| {
+            let status = Cell::new(None);
+            client
+                .exchange_refresh_token(&refresh_token)
+                .request(|request| Self::http_client_with_status(request, &status))
+                .map_err(|error| Self::token_error(error, status.get()))
+        })?;
+
+        Ok(LidlApi::from_token_response(
+            endpoints.clone(),
+            country_code,
+            language_code,
+            &token_response,
+        )?
+        .with_retry_policy(retry_policy.clone()))
     }
 
     fn http_client(
@@ -236,4 +468,33 @@ impl OAuthFlow {
             .insert(AUTHORIZATION, OAUTH_AUTHORIZATION_HEADER.parse().unwrap());
         oauth2::reqwest::http_client(request)
     }
+
+    /// Like [`Self::http_client`], but also keeps the status of the response,
+    /// since oauth2 does not include it in its errors
+    fn http_client_with_status(
+        request: oauth2::HttpRequest,
+        status: &Cell<Option<StatusCode>>,
+    ) -> Result<oauth2::HttpResponse, oauth2::reqwest::Error<reqwest::Error>> {
+        let response = Self::http_client(request)?;
+        status.set(Some(response.status_code));
+        Ok(response)
+    }
+
+    fn token_error(
+        error: RequestTokenError<oauth2::reqwest::Error<reqwest::Error>, BasicErrorResponse>,
+        status: Option<StatusCode>,
+    ) -> Error {
+        match error {
+            RequestTokenError::ServerResponse(response) => match response.error() {
+                BasicErrorResponseType::InvalidGrant
+                | BasicErrorResponseType::InvalidClient
+                | BasicErrorResponseType::UnauthorizedClient => Error::Unauthorized,
+                _ => Error::UnexpectedPayload(response.to_string()),
+            },
+            RequestTokenError::Request(error) => Error::Connection(error.to_string()),
+            error => status
+                .and_then(Error::from_status)
+                .unwrap_or_else(|| Error::UnexpectedPayload(error.to_string())),
+        }
+    }
 }
@@ -0,0 +1,293 @@
+use ir::{DataSource, ItemSources, ReceiptDetailed, ReceiptItem, ReceiptSources};
+
+/// Merge a receipt from the v2 API with the same receipt parsed from the HTML of the v3 API.
+/// The v2 API has the real barcodes of the products, while the HTML has their tax categories
+/// and reliable discounts (with promotion ids), so each field of each line is taken from
+/// the source that has it, and the source is recorded in the merged receipt.
+/// The HTML only completes the lines of the v2 receipt, line by line, so if the lines or
+/// totals of both receipts do not match, the v2 receipt is kept as-is and a warning is printed.
+pub(crate) fn merge_receipts(api: ReceiptDetailed, html: ReceiptDetailed) -> ReceiptDetailed {
+    if !is_same_receipt(&api, &html) {
+        eprintln!(
+            "WARNING: The HTML of receipt {} does not match its lines, so it is not used to \
+             complete them",
+            api.id
+        );
+        return api;
+    }
+
+    let items = api
+        .items
+        .into_iter()
+        .zip(html.items)
+        .map(|(api_item, html_item)| merge_items(api_item, html_item))
+        .collect();
+
+    let (taxes, taxes_source) = if html.taxes.is_empty() {
+        (api.taxes, DataSource::Api)
+    } else {
+        (html.taxes, DataSource::Html)
+    };
+    let (total_discount, total_discount_source) = match html.total_discount {
+        Some(total_discount) => (Some(total_discount), DataSource::Html),
+        None => (api.total_discount, DataSource::Api),
+    };
+    let (tenders, tenders_source) = if api.tenders.is_empty() {
+        (html.tenders, DataSource::Html)
+    } else {
+        (api.tenders, DataSource::Api)
+    };
+
+    // the v2 API only has the id and name of the store, and the HTML also has its address
+    let mut store = api.store;
+    if store.address.is_empty() {
+        store.address = html.store.address;
+    }
+    if store.country.is_none() {
+        store.country = html.store.country;
+    }
+
+    let mut receipt = ReceiptDetailed {
+        items,
+        store,
+        taxes,
+        total: api.total.or(html.total),
+        total_discount,
+        language: api.language.or(html.language),
+        tenders,
+        sources: Some(ReceiptSources {
+            taxes: taxes_source,
+            total_discount: total_discount_source,
+            tenders: tenders_source,
+        }),
+        ..api
+    };
+    receipt.fill_tax_rates();
+    receipt
+}
+
+/// Whether both receipts have the same lines, in the same order, and the same total
+fn is_same_receipt(api: &ReceiptDetailed, html: &ReceiptDetailed) -> bool {
+    let same_total = match (&api.total, &html.total) {
+        (Some(api_total), Some(html_total)) => api_total.amount == html_total.amount,
+        _ => true,
+    };
+
+    same_total
+        && api.items.len() == html.items.len()
+        && api
+            .items
+            .iter()
+            .zip(&html.items)
+            .all(|(api_item, html_item)| is_same_line(api_item, html_item))
+}
+
+/// Whether two lines from different sources are the same line of the receipt;
+/// names are not compared, since they are shortened differently in each source
+fn is_same_line(api_item: &ReceiptItem, html_item: &ReceiptItem) -> bool {
+    api_item.unit_price.amount == html_item.unit_price.amount
+        && api_item.quantity == html_item.quantity
+}
+
+fn merge_items(api_item: ReceiptItem, html_item: ReceiptItem) -> ReceiptItem {
+    let (barcode, barcode_source) = if api_item.barcode.is_empty() {
+        (html_item.barcode, DataSource::Html)
+    } else {
+        (api_item.barcode, DataSource::Api)
+    };
+    let (tax, tax_source) = match html_item.tax {
+        Some(tax) => (Some(tax), DataSource::Html),
+        None => (api_item.tax, DataSource::Api),
+    };
+    let (discounts, discounts_source) = if html_item.discounts.is_empty() {
+        (api_item.discounts, DataSource::Api)
+    } else {
+        (html_item.discounts, DataSource::Html)
+    };
+    // the API only says whether a product is sold by weight, while the HTML has the unit
+    let unit = if html_item.unit.is_measured() {
+        html_item.unit
+    } else {
+        api_item.unit
+    };
+
+    ReceiptItem {
+        barcode,
+        tax,
+        discounts,
+        unit,
+        sources: Some(ItemSources {
+            barcode: barcode_source,
+            tax: tax_source,
+            discounts: discounts_source,
+        }),
+        ..api_item
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ir::{
+        test_util::{self, money, receipt},
+        DataSource, Discount, DiscountKind, ItemSources, ReceiptItem, ReceiptSources, Tax,
+    };
+
+    use super::merge_receipts;
+
+    fn item(name: &str, unit_price: i64, barcode: &str) -> ReceiptItem {
+        ReceiptItem {
+            barcode: barcode.to_owned(),
+            ..test_util::item(name, unit_price)
+        }
+    }
+
+    #[test]
+    fn test_merge_receipts() {
+        let tax = Tax {
+            category: "A".to_owned(),
+            rate: None,
+        };
+        let discount = Discount {
+            amount: money(50),
+            description: Some("Lidl Plus Rabatt".to_owned()),
+            promotion_id: Some("100001234".to_owned()),
+            kind: DiscountKind::Loyalty,
+        };
+
+        let api = receipt(vec![
+            ReceiptItem {
+                discounts: vec![Discount {
+                    promotion_id: None,
+                    ..discount.clone()
+                }],
+                ..item("Tomaten", 299, "4056489123456")
+            },
+            item("Paprika rot", 159, "4056489654321"),
+            item("Sojajoghurt", 95, ""),
+        ]);
+        let mut html = receipt(vec![
+            ReceiptItem {
+                tax: Some(tax.clone()),
+                discounts: vec![discount.clone()],
+                ..item("Cherrystrauchtomaten", 299, "lidl-0082388")
+            },
+            ReceiptItem {
+                tax: Some(tax.clone()),
+                ..item("Paprika rot", 159, "lidl-0082620")
+            },
+            ReceiptItem {
+                tax: Some(tax.clone()),
+                ..item("Sojajoghurt Natur", 95, "lidl-0165195")
+            },
+        ]);
+        html.store.address = vec!["Hauptstraße 1".to_owned(), "12345 Berlin".to_owned()];
+        html.store.country = Some("DE".to_owned());
+        html.language = Some("de".to_owned());
+
+        let merged = merge_receipts(api, html);
+        assert_eq!(
+            merged.items,
+            vec![
+                ReceiptItem {
+                    tax: Some(tax.clone()),
+                    discounts: vec![discount],
+                    sources: Some(ItemSources {
+                        barcode: DataSource::Api,
+                        tax: DataSource::Html,
+                        discounts: DataSource::Html,
+                    }),
+                    ..item("Tomaten", 299, "4056489123456")
+                },
+                // lines without discounts in either source keep the ones of the API
+                ReceiptItem {
+                    tax: Some(tax.clone()),
+                    sources: Some(ItemSources {
+                        barcode: DataSource::Api,
+                        tax: DataSource::Html,
+                        discounts: DataSource::Api,
+                    }),
+                    ..item("Paprika rot", 159, "4056489654321")
+                },
+                ReceiptItem {
+                    tax: Some(tax),
+                    sources: Some(ItemSources {
+                        barcode: DataSource::Html,
+                        tax: DataSource::Html,
+                        discounts: DataSource::Api,
+                    }),
+                    ..item("Sojajoghurt", 95, "lidl-0165195")
+                },
+            ]
+        );
+        assert_eq!(
+            merged.sources,
+            Some(ReceiptSources {
+                taxes: DataSource::Api,
+                total_discount: DataSource::Api,
+                tenders: DataSource::Html,
+            })
+        );
+        assert_eq!(merged.store.name, "Example Store");
+        assert_eq!(merged.store.address, vec!["Hauptstraße 1", "12345 Berlin"]);
+        assert_eq!(merged.store.country.as_deref(), Some("DE"));
+        assert_eq!(merged.language.as_deref(), Some("de"));
+    }
+
+    #[test]
+    fn test_merge_receipts_same_price() {
+        let tax = |category: &str| {
+            Some(Tax {
+                category: category.to_owned(),
+                rate: None,
+            })
+        };
+
+        // lines with the same price are merged in the order they are printed
+        let api = receipt(vec![
+            item("Milch", 109, "4056489000001"),
+            item("Brot", 109, "4056489000002"),
+        ]);
+        let html = receipt(vec![
+            ReceiptItem {
+                tax: tax("A"),
+                ..item("Frische Milch", 109, "lidl-0000001")
+            },
+            ReceiptItem {
+                tax: tax("B"),
+                ..item("Bauernbrot", 109, "lidl-0000002")
+            },
+        ]);
+
+        let merged = merge_receipts(api, html);
+        assert_eq!(merged.items.len(), 2);
+        assert_eq!(merged.items[0].barcode, "4056489000001");
+        assert_eq!(merged.items[0].tax, tax("A"));
+        assert_eq!(merged.items[1].barcode, "4056489000002");
+        assert_eq!(merged.items[1].tax, tax("B"));
+    }
+
+    #[test]
+    fn test_merge_receipts_mismatch() {
+        let api = receipt(vec![
+            item("Milch", 109, "4056489000001"),
+            item("Brot", 109, "4056489000002"),
+        ]);
+
+        // a line that is only in the HTML is never added to the receipt
+        let html = receipt(vec![
+            item("Frische Milch", 109, "lidl-0000001"),
+            item("Pfand", 25, ""),
+            item("Bauernbrot", 109, "lidl-0000002"),
+        ]);
+        assert_eq!(merge_receipts(api.clone(), html), api);
+
+        let mut html = receipt(vec![
+            item("Frische Milch", 109, "lidl-0000001"),
+            item("Bauernbrot", 109, "lidl-0000002"),
+        ]);
+        let mut api_with_total = api.clone();
+        api_with_total.total = Some(money(218));
+        html.total = Some(money(243));
+        assert_eq!(merge_receipts(api_with_total.clone(), html), api_with_total);
+    }
+}
@@ -0,0 +1,84 @@
+use std::{thread, time::Duration};
+
+use crate::error::Error;
+
+/// How requests that fail with a transient error (see [`Error::is_transient`]) are retried
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of times a request is made, including the first one
+    pub max_attempts: u32,
+    /// Time to wait before the first retry, which doubles with each following retry
+    pub initial_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Run `request` until it succeeds, fails with an error that is not transient,
+    /// or the maximum number of attempts is reached
+    pub(crate) fn run<T>(&self, mut request: impl FnMut() -> Result<T, Error>) -> Result<T, Error> {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match request() {
+                Err(error) if error.is_transient() && attempt < self.max_attempts => {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::error::Error;
+
+    use super::RetryPolicy;
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::ZERO,
+        };
+
+        let mut attempts = 0;
+        let result = policy.run(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(Error::Server(503))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert!(matches!(result, Ok(3)));
+
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run(|| {
+            attempts += 1;
+            Err(Error::RateLimited)
+        });
+        assert!(matches!(result, Err(Error::RateLimited)));
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run(|| {
+            attempts += 1;
+            Err(Error::Unauthorized)
+        });
+        assert!(matches!(result, Err(Error::Unauthorized)));
+        assert_eq!(attempts, 1);
+    }
+}
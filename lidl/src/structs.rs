@@ -1,6 +1,8 @@
-use std::{fmt::Display, num::ParseFloatError};
+use std::fmt::Display;
 
 use chrono::{DateTime, NaiveDateTime, Utc};
+use ir::money::parse_decimal;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,17 +41,36 @@ pub struct Language {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ReceiptsPage {
+    pub page: Option<u32>,
+    pub size: Option<u32>,
+    pub total_count: Option<u32>,
     #[serde(rename = "tickets")]
     pub receipts: Vec<Receipt>,
 }
 
+impl From<ReceiptsPage> for ir::ReceiptPage {
+    fn from(value: ReceiptsPage) -> Self {
+        let has_more = match (value.page, value.size, value.total_count) {
+            (Some(page), Some(size), Some(total_count)) => page * size < total_count,
+            // if the page does not say how many receipts there are, keep going until
+            // there is an empty page
+            _ => !value.receipts.is_empty(),
+        };
+
+        Self {
+            has_more,
+            receipts: value.receipts.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct Receipt {
     pub id: String,
     pub date: DateTime<Utc>,
     pub currency: Currency,
-    pub total_amount: f64,
+    pub total_amount: Decimal,
     pub store_code: String,
     pub articles_count: u32,
 }
@@ -59,9 +80,9 @@ impl From<Receipt> for ir::ReceiptSummary {
         Self {
             id: value.id,
             date: value.date.naive_utc(), // Lidl sends local date with utc offset (which is wrong)
-            currency: value.currency.into(),
-            total_amount: value.total_amount,
+            total_amount: ir::Money::new(value.total_amount, value.currency.into()),
             articles_count: Some(value.articles_count),
+            store_id: Some(value.store_code),
         }
     }
 }
@@ -84,20 +105,25 @@ impl From<Currency> for ir::Currency {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-// The Lidl API returns floats and integers as strings,
+// The Lidl API returns decimals and integers as strings,
 // so we generalize this struct to accept both and convert
-// everything to floats after deserializing
+// everything to decimals after deserializing
 pub(crate) struct ReceiptDetailed<T> {
     pub id: String,
     pub items_line: Vec<ReceiptItem<T>>,
     pub date: NaiveDateTime,
-    pub total_amount_numeric: f64,
+    pub total_amount_numeric: Decimal,
+    pub total_discount: Option<T>,
     pub currency: Currency,
     pub store: Store,
+    #[serde(default)]
+    pub taxes: Vec<Tax<T>>,
+    #[serde(default)]
+    pub payments: Vec<Payment<T>>,
 }
 
-impl TryFrom<ReceiptDetailed<String>> for ReceiptDetailed<f64> {
-    type Error = ParseFloatError;
+impl TryFrom<ReceiptDetailed<String>> for ReceiptDetailed<Decimal> {
+    type Error = ir::error::Error;
 
     fn try_from(value: ReceiptDetailed<String>) -> Result<Self, Self::Error> {
         Ok(Self {
@@ -109,21 +135,58 @@ impl TryFrom<ReceiptDetailed<String>> for ReceiptDetailed<f64> {
                 .collect::<Result<Vec<_>, _>>()?,
             date: value.date,
             total_amount_numeric: value.total_amount_numeric,
+            total_discount: value
+                .total_discount
+                .map(|discount| parse_decimal(&discount))
+                .transpose()?,
             currency: value.currency,
             store: value.store,
+            taxes: value
+                .taxes
+                .into_iter()
+                .map(|tax| tax.try_into())
+                .collect::<Result<Vec<_>, _>>()?,
+            payments: value
+                .payments
+                .into_iter()
+                .map(|payment| payment.try_into())
+                .collect::<Result<Vec<_>, _>>()?,
         })
     }
 }
 
-impl From<ReceiptDetailed<f64>> for ir::ReceiptDetailed {
-    fn from(value: ReceiptDetailed<f64>) -> Self {
-        Self {
+impl From<ReceiptDetailed<Decimal>> for ir::ReceiptDetailed {
+    fn from(value: ReceiptDetailed<Decimal>) -> Self {
+        let currency: ir::Currency = value.currency.into();
+        let mut receipt = Self {
             id: value.id,
-            items: value.items_line.into_iter().map(Into::into).collect(),
+            items: value
+                .items_line
+                .into_iter()
+                .map(|item| item.into_ir(&currency))
+                .collect(),
             date: value.date,
-            currency: value.currency.into(),
             store: value.store.into(),
-        }
+            taxes: value
+                .taxes
+                .into_iter()
+                .map(|tax| tax.into_ir(&currency))
+                .collect(),
+            total: Some(ir::Money::new(value.total_amount_numeric, currency.clone())),
+            total_discount: value
+                .total_discount
+                .map(|discount| ir::Money::new(discount.abs(), currency.clone())),
+            language: None,
+            tenders: value
+                .payments
+                .into_iter()
+                .map(|payment| payment.into_ir(&currency))
+                .collect(),
+            sources: None,
+            currency,
+        };
+        receipt.fill_tax_rates();
+        receipt
     }
 }
 
@@ -137,17 +200,18 @@ pub(crate) struct ReceiptItem<T> {
     pub name: String,
     pub code_input: String,
     pub discounts: Vec<Discount<T>>,
+    pub tax_group_name: Option<String>,
 }
 
-impl TryFrom<ReceiptItem<String>> for ReceiptItem<f64> {
-    type Error = ParseFloatError;
+impl TryFrom<ReceiptItem<String>> for ReceiptItem<Decimal> {
+    type Error = ir::error::Error;
 
     fn try_from(value: ReceiptItem<String>) -> Result<Self, Self::Error> {
         Ok(Self {
-            current_unit_price: value.current_unit_price.replace(',', ".").parse()?,
-            quantity: value.quantity.replace(',', ".").parse()?,
+            current_unit_price: parse_decimal(&value.current_unit_price)?,
+            quantity: parse_decimal(&value.quantity)?,
             is_weight: value.is_weight,
-            original_amount: value.original_amount.replace(',', ".").parse()?,
+            original_amount: parse_decimal(&value.original_amount)?,
             name: value.name,
             code_input: value.code_input,
             discounts: value
@@ -155,19 +219,39 @@ impl TryFrom<ReceiptItem<String>> for ReceiptItem<f64> {
                 .into_iter()
                 .map(|discount| discount.try_into())
                 .collect::<Result<Vec<_>, _>>()?,
+            tax_group_name: value.tax_group_name,
         })
     }
 }
 
-impl From<ReceiptItem<f64>> for ir::ReceiptItem {
-    fn from(value: ReceiptItem<f64>) -> Self {
-        Self {
-            unit_price: value.current_unit_price,
-            quantity: value.quantity,
-            is_weight: value.is_weight,
-            name: value.name,
-            barcode: value.code_input,
-            discounts: value.discounts.into_iter().map(Into::into).collect(),
+impl ReceiptItem<Decimal> {
+    fn into_ir(self, currency: &ir::Currency) -> ir::ReceiptItem {
+        ir::ReceiptItem {
+            kind: ir::ItemKind::from_description(
+                &self.name,
+                self.current_unit_price.is_sign_negative(),
+            ),
+            unit_price: ir::Money::new(self.current_unit_price, currency.clone()),
+            quantity: self.quantity,
+            // the API does not say the unit of products sold by weight, which are weighed
+            // in kilograms
+            unit: if self.is_weight {
+                ir::Unit::Kilogram
+            } else {
+                ir::Unit::Piece
+            },
+            name: self.name,
+            barcode: self.code_input,
+            discounts: self
+                .discounts
+                .into_iter()
+                .map(|discount| discount.into_ir(currency))
+                .collect(),
+            tax: self.tax_group_name.map(|category| ir::Tax {
+                category,
+                rate: None,
+            }),
+            sources: None,
         }
     }
 }
@@ -177,23 +261,91 @@ impl From<ReceiptItem<f64>> for ir::ReceiptItem {
 pub(crate) struct Discount<T> {
     pub description: String,
     pub amount: T,
+    pub promotion_id: Option<String>,
 }
 
-impl TryFrom<Discount<String>> for Discount<f64> {
-    type Error = ParseFloatError;
+impl TryFrom<Discount<String>> for Discount<Decimal> {
+    type Error = ir::error::Error;
 
     fn try_from(value: Discount<String>) -> Result<Self, Self::Error> {
         Ok(Self {
             description: value.description,
-            amount: value.amount.replace(',', ".").parse()?,
+            amount: parse_decimal(&value.amount)?,
+            promotion_id: value.promotion_id,
         })
     }
 }
 
-impl From<Discount<f64>> for ir::Discount {
-    fn from(value: Discount<f64>) -> Self {
-        Self {
-            amount: value.amount,
+impl Discount<Decimal> {
+    fn into_ir(self, currency: &ir::Currency) -> ir::Discount {
+        ir::Discount {
+            amount: ir::Money::new(self.amount.abs(), currency.clone()),
+            kind: ir::DiscountKind::from_description(&self.description),
+            description: Some(self.description),
+            promotion_id: self.promotion_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Tax<T> {
+    pub tax_group_name: String,
+    pub percentage: T,
+    pub taxable_amount: T,
+    pub amount: T,
+}
+
+impl TryFrom<Tax<String>> for Tax<Decimal> {
+    type Error = ir::error::Error;
+
+    fn try_from(value: Tax<String>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            tax_group_name: value.tax_group_name,
+            percentage: parse_decimal(&value.percentage)?,
+            taxable_amount: parse_decimal(&value.taxable_amount)?,
+            amount: parse_decimal(&value.amount)?,
+        })
+    }
+}
+
+impl Tax<Decimal> {
+    fn into_ir(self, currency: &ir::Currency) -> ir::TaxSummary {
+        ir::TaxSummary {
+            category: self.tax_group_name,
+            rate: self.percentage,
+            base_amount: ir::Money::new(self.taxable_amount, currency.clone()),
+            tax_amount: ir::Money::new(self.amount, currency.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Payment<T> {
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub description: Option<String>,
+    pub amount: T,
+}
+
+impl TryFrom<Payment<String>> for Payment<Decimal> {
+    type Error = ir::error::Error;
+
+    fn try_from(value: Payment<String>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            kind: value.kind,
+            description: value.description,
+            amount: parse_decimal(&value.amount)?,
+        })
+    }
+}
+
+impl Payment<Decimal> {
+    fn into_ir(self, currency: &ir::Currency) -> ir::Tender {
+        ir::Tender {
+            method: self.description.or(self.kind).unwrap_or_default(),
+            amount: ir::Money::new(self.amount, currency.clone()),
         }
     }
 }
@@ -210,6 +362,8 @@ impl From<Store> for ir::Store {
         Self {
             id: value.id,
             name: value.name,
+            address: vec![],
+            country: None,
         }
     }
 }
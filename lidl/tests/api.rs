@@ -0,0 +1,393 @@
+//! Tests of the Lidl Plus API client against a local server that serves recorded
+//! responses from `test/api`, so that changes to the API show up as failing tests.
+
+use std::{env, fs, thread, time::Duration};
+
+use anyhow::Result;
+use chrono::Utc;
+use ir::{DataSource, DiscountKind, ItemSources, ReceiptFilter, ReceiptSummary, StoreApi, Unit};
+use lidl::{
+    error::Error, get_countries, Endpoints, LidlApi, OAuthFlow, ResponseArchive, RetryPolicy,
+};
+use rust_decimal::Decimal;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const ACCESS_TOKEN: &str = "test-access-token";
+const REFRESH_TOKEN: &str = "test-refresh-token";
+
+/// Start a server in the background that behaves like the Lidl Plus services,
+/// and get the endpoints to reach it
+fn start_server() -> Endpoints {
+    start_failing_server(200, 0)
+}
+
+/// Like [`start_server`], but the first `failures` requests to the tickets API
+/// are answered with `status`
+fn start_failing_server(status: u16, mut failures: u32) -> Endpoints {
+    let server = Server::http("127.0.0.1:0").expect("server should start");
+    let address = server
+        .server_addr()
+        .to_ip()
+        .expect("server should listen on an ip address");
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            if failures > 0 && request.url().starts_with("/api/") {
+                failures -= 1;
+                let _ = request.respond(Response::empty(status));
+            } else {
+                respond(request);
+            }
+        }
+    });
+
+    Endpoints::with_base_url(&format!("http://{}", address))
+}
+
+fn respond(mut request: Request) {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let is_authorized = request.headers().iter().any(|header| {
+        header.field.equiv("Authorization")
+            && header.value.as_str() == format!("Bearer {}", ACCESS_TOKEN)
+    });
+    let mut body = String::new();
+    let _ = request.as_reader().read_to_string(&mut body);
+
+    let (status, response) = match (request.method(), path.as_str()) {
+        (Method::Get, "/configurationapp/v3/countries") => {
+            (200, include_str!("../test/api/countries.json"))
+        }
+        (Method::Post, "/connect/token")
+            if body.contains(&format!("refresh_token={}", REFRESH_TOKEN)) =>
+        {
+            (200, include_str!("../test/api/token.json"))
+        }
+        (Method::Post, "/connect/token") => (400, r#"{ "error": "invalid_grant" }"#),
+        (Method::Get, _) if path.starts_with("/api/") && !is_authorized => (401, ""),
+        (Method::Get, "/api/v2/SE/tickets") => (200, include_str!("../test/api/tickets.json")),
+        (Method::Get, "/api/v2/XX/tickets") => (200, r#"{ "message": "Unknown country" }"#),
+        (Method::Get, "/api/v2/SE/tickets/v2-ticket") => {
+            (200, include_str!("../test/api/ticket_v2.json"))
+        }
+        (Method::Get, "/api/v3/SE/tickets/v2-ticket") => {
+            (200, include_str!("../test/api/ticket_v3_with_v2.json"))
+        }
+        (Method::Get, "/api/v3/SE/tickets/html-ticket") => {
+            (200, include_str!("../test/api/ticket_v3.json"))
+        }
+        _ => (404, ""),
+    };
+
+    let content_type =
+        Header::from_bytes("Content-Type", "application/json").expect("header should be valid");
+    let _ = request.respond(
+        Response::from_string(response)
+            .with_status_code(status)
+            .with_header(content_type),
+    );
+}
+
+fn login(endpoints: &Endpoints) -> Result<LidlApi> {
+    OAuthFlow::get_token_from_refresh_token(
+        endpoints,
+        "SE".to_string(),
+        "sv".to_string(),
+        REFRESH_TOKEN.to_string(),
+        &RetryPolicy::default(),
+    )
+}
+
+fn receipt_summary(api: &LidlApi, id: &str) -> Result<ReceiptSummary> {
+    Ok(api
+        .get_available_receipts()?
+        .into_iter()
+        .find(|receipt| receipt.id == id)
+        .expect("receipt should be listed"))
+}
+
+#[test]
+fn test_get_countries() -> Result<()> {
+    let endpoints = start_server();
+
+    let countries = get_countries(&endpoints, &RetryPolicy::default())?;
+    assert_eq!(countries.len(), 2);
+    assert_eq!(countries[1].id, "SE");
+    assert_eq!(
+        countries[1]
+            .get_default_language()
+            .map(|language| language.id),
+        Some("sv".to_string())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_refresh_token() -> Result<()> {
+    let endpoints = start_server();
+
+    let api = login(&endpoints)?;
+    assert_eq!(api.get_refresh_token(), "test-new-refresh-token");
+    assert_eq!(api.get_country_code(), "SE");
+    assert_eq!(api.get_access_token(), ACCESS_TOKEN);
+    let expiry = api
+        .get_access_token_expiry()
+        .expect("token response should have an expiry");
+    assert!(expiry > Utc::now() + chrono::Duration::minutes(59));
+
+    Ok(())
+}
+
+#[test]
+fn test_cached_access_token() -> Result<()> {
+    let endpoints = start_server();
+
+    // the access token is used as-is, without going through the token endpoint
+    let api = LidlApi::from_access_token(
+        endpoints.clone(),
+        "SE".to_string(),
+        "sv".to_string(),
+        ACCESS_TOKEN.to_string(),
+        None,
+        "revoked-refresh-token".to_string(),
+    )?;
+    assert_eq!(api.get_receipts_page(1)?.receipts.len(), 2);
+
+    let api = LidlApi::from_access_token(
+        endpoints,
+        "SE".to_string(),
+        "sv".to_string(),
+        "expired-access-token".to_string(),
+        None,
+        REFRESH_TOKEN.to_string(),
+    )?;
+    let error = api
+        .get_receipts_page(1)
+        .expect_err("expired access token should not be accepted");
+    assert!(matches!(error.downcast_ref(), Some(Error::Unauthorized)));
+
+    Ok(())
+}
+
+#[test]
+fn test_refresh_token_revoked() {
+    let endpoints = start_server();
+
+    let error = OAuthFlow::get_token_from_refresh_token(
+        &endpoints,
+        "SE".to_string(),
+        "sv".to_string(),
+        "revoked-refresh-token".to_string(),
+        &RetryPolicy::default(),
+    )
+    .err()
+    .expect("revoked refresh token should not be accepted");
+    assert!(matches!(error.downcast_ref(), Some(Error::Unauthorized)));
+}
+
+#[test]
+fn test_retry_transient_errors() -> Result<()> {
+    let retry_policy = RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(1),
+    };
+
+    let endpoints = start_failing_server(503, 2);
+    let api = login(&endpoints)?.with_retry_policy(retry_policy.clone());
+    assert_eq!(api.get_receipts_page(1)?.receipts.len(), 2);
+
+    let endpoints = start_failing_server(429, 3);
+    let api = login(&endpoints)?.with_retry_policy(retry_policy.clone());
+    let error = api
+        .get_receipts_page(1)
+        .expect_err("requests should be rate limited");
+    assert!(matches!(error.downcast_ref(), Some(Error::RateLimited)));
+
+    // an unauthorized request cannot succeed later, so it is not retried
+    let endpoints = start_failing_server(401, 1);
+    let api = login(&endpoints)?.with_retry_policy(retry_policy);
+    let error = api
+        .get_receipts_page(1)
+        .expect_err("request should be unauthorized");
+    assert!(matches!(error.downcast_ref(), Some(Error::Unauthorized)));
+
+    Ok(())
+}
+
+#[test]
+fn test_unexpected_payload() -> Result<()> {
+    let endpoints = start_server();
+    let api = OAuthFlow::get_token_from_refresh_token(
+        &endpoints,
+        "XX".to_string(),
+        "xx".to_string(),
+        REFRESH_TOKEN.to_string(),
+        &RetryPolicy::default(),
+    )?;
+
+    let error = api
+        .get_receipts_page(1)
+        .expect_err("payload should not be parsed");
+    assert!(matches!(
+        error.downcast_ref(),
+        Some(Error::UnexpectedPayload(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_list_receipts() -> Result<()> {
+    let endpoints = start_server();
+    let api = login(&endpoints)?;
+
+    let page = api.get_receipts_page(1)?;
+    assert!(!page.has_more);
+    assert_eq!(page.receipts.len(), 2);
+    assert_eq!(page.receipts[0].id, "v2-ticket");
+    assert_eq!(page.receipts[0].total_amount.amount, Decimal::new(21686, 2));
+    assert_eq!(page.receipts[0].total_amount.currency.id, "SEK");
+    assert_eq!(page.receipts[0].articles_count, Some(2));
+    assert_eq!(page.receipts[0].store_id.as_deref(), Some("STORE123"));
+
+    let filter = ReceiptFilter {
+        from: chrono::NaiveDate::from_ymd_opt(2024, 1, 10),
+        ..Default::default()
+    };
+    let receipts = api.list_receipts(filter).collect::<Result<Vec<_>>>()?;
+    assert_eq!(receipts.len(), 1);
+    assert_eq!(receipts[0].id, "v2-ticket");
+
+    Ok(())
+}
+
+#[test]
+fn test_get_v2_receipt() -> Result<()> {
+    let endpoints = start_server();
+    let api = login(&endpoints)?;
+
+    let summary = receipt_summary(&api, "v2-ticket")?;
+    let receipt = api.get_specific_receipt_with_html(&summary)?;
+    assert_eq!(receipt.id, "v2-ticket");
+    assert_eq!(receipt.store.name, "Example Store");
+    assert_eq!(receipt.items.len(), 2);
+
+    let item = &receipt.items[0];
+    assert_eq!(item.name, "Grytbitar");
+    assert_eq!(item.barcode, "4056489123456");
+    assert_eq!(item.quantity, Decimal::new(2, 0));
+    assert_eq!(item.unit_price.amount, Decimal::new(7990, 2));
+    assert_eq!(item.discounts[0].amount.amount, Decimal::new(792, 2));
+    assert_eq!(item.discounts[0].kind, DiscountKind::Loyalty);
+    assert_eq!(
+        item.tax.as_ref().and_then(|tax| tax.rate),
+        Some(Decimal::new(12, 0))
+    );
+    assert_eq!(receipt.items[1].unit, Unit::Kilogram);
+
+    // the barcodes come from the v2 API, and the rest from the HTML receipt of the v3 API
+    assert_eq!(
+        item.sources,
+        Some(ItemSources {
+            barcode: DataSource::Api,
+            tax: DataSource::Html,
+            discounts: DataSource::Html,
+        })
+    );
+    assert_eq!(receipt.items[1].barcode, "7006839");
+
+    assert_eq!(receipt.tenders[0].method, "Kort");
+    assert!(receipt.reconcile()?.is_balanced());
+
+    // without the HTML receipt, only the v2 API is used
+    let receipt = api.get_specific_receipt(&summary)?;
+    assert_eq!(receipt.items[0].barcode, "4056489123456");
+    assert_eq!(receipt.items[0].sources, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_get_html_receipt() -> Result<()> {
+    let endpoints = start_server();
+    let api = login(&endpoints)?;
+
+    // the v2 endpoint does not have this receipt, so it must be read from the v3 one
+    let receipt = api.get_specific_receipt(&receipt_summary(&api, "html-ticket")?)?;
+    assert_eq!(receipt.id, "html-ticket");
+    assert_eq!(receipt.store.id, "STORE123");
+    assert_eq!(receipt.items.len(), 16);
+    assert_eq!(receipt.items[0].barcode, "lidl-0051496");
+    assert_eq!(
+        receipt.total.as_ref().map(|total| total.amount),
+        Some(Decimal::new(47924, 2))
+    );
+    assert!(receipt.reconcile()?.is_balanced());
+
+    Ok(())
+}
+
+#[test]
+fn test_get_receipt_v2_failing() -> Result<()> {
+    let summary = receipt_summary(&login(&start_server())?, "v2-ticket")?;
+
+    // the v2 endpoint fails to answer, so the receipt is read from the v3 one alone
+    let endpoints = start_failing_server(500, 1);
+    let api = login(&endpoints)?.with_retry_policy(RetryPolicy {
+        max_attempts: 1,
+        initial_backoff: Duration::ZERO,
+    });
+    let receipt = api.get_specific_receipt(&summary)?;
+    assert_eq!(receipt.id, "v2-ticket");
+    assert_eq!(receipt.items[0].name, "Grytbitar");
+    assert_eq!(receipt.items[0].sources, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_response_archive() -> Result<()> {
+    let dir = env::temp_dir().join(format!("lidl-archive-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let endpoints = start_server();
+    let api = login(&endpoints)?.with_archive(ResponseArchive::new(&dir));
+
+    let v2_receipt = api.get_specific_receipt_with_html(&receipt_summary(&api, "v2-ticket")?)?;
+    let html_receipt = api.get_specific_receipt(&receipt_summary(&api, "html-ticket")?)?;
+    assert!(dir.join("pages/1.json").is_file());
+    assert!(dir.join("receipts/v2-ticket/v2.json").is_file());
+    assert!(dir.join("receipts/v2-ticket/v3.json").is_file());
+    assert!(!dir.join("receipts/html-ticket/v2.json").exists());
+
+    // the archived receipts are listed and parsed again just like the fetched ones
+    let archive = ResponseArchive::new(&dir);
+    let receipts = archive.get_available_receipts()?;
+    assert_eq!(
+        receipts
+            .iter()
+            .map(|receipt| &receipt.id)
+            .collect::<Vec<_>>(),
+        vec!["v2-ticket", "html-ticket"]
+    );
+    assert_eq!(archive.get_specific_receipt(&receipts[0])?, v2_receipt);
+    assert_eq!(archive.get_specific_receipt(&receipts[1])?, html_receipt);
+
+    let error = archive
+        .get_specific_receipt(&ReceiptSummary {
+            id: "../html-ticket".to_string(),
+            ..receipts[1].clone()
+        })
+        .expect_err("receipt id should not leave the archive");
+    assert!(matches!(
+        error.downcast_ref(),
+        Some(Error::InvalidReceiptId(_))
+    ));
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
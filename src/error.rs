@@ -1,9 +1,28 @@
+use rust_decimal::Decimal;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("No default language is available for the selected country")]
     LidlNoDefaultLanguageForCountry,
+    #[error("No archive of Lidl responses was given, with --archive or in the configuration")]
+    NoArchiveDir,
+    #[error("No receipts were found")]
+    NoReceiptsFound,
+    #[error("The symbol of currency {0} is not known, please give it with --currency-symbol")]
+    UnknownCurrencySymbol(String),
+    #[error("Import was aborted because the receipt was already imported")]
+    ReceiptAlreadyImported,
+    #[error("Import was aborted because the receipt does not add up to its totals")]
+    ReceiptNotReconciled,
+    #[error("Store {0} is not mapped to a shopping location of Grocy")]
+    StoreNotMapped(String),
+    #[error("No imports into Grocy were recorded")]
+    NoImportsFound,
+    #[error("No import of receipt {0} into Grocy was recorded")]
+    ImportNotFound(String),
+    #[error("{0} change(s) of the import could not be undone, and were kept to try again")]
+    UndoIncomplete(usize),
     #[error("Product has been skipped")]
     SkippedProduct,
     #[error("Expected barcode associated with its product to have an amount, but it didn't")]
@@ -14,4 +33,8 @@ pub enum Error {
     BarcodeQuantityUnitUnsupported(u32),
     #[error("Product has tare weight handling enabled, but that is not supported by this program")]
     ProductHasTareWeightHandling,
+    #[error("Product quantity ({0}) is not a positive whole number")]
+    UnsupportedQuantity(Decimal),
+    #[error("Cannot calculate price of product for an amount of {0}")]
+    UnsupportedAmount(f64),
 }
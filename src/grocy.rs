@@ -1,26 +1,50 @@
 use colored::Colorize;
-use ir::{Currency, ReceiptDetailed, ReceiptItem, Store};
-use std::{collections::HashMap, fmt::Display};
+use ir::{DiscountKind, Money, ProductLine, ReceiptDetailed, ReceiptItem, Store, Unit};
+use rust_decimal::{
+    prelude::{FromPrimitive, ToPrimitive},
+    Decimal,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
 
 use anyhow::Result;
-use chrono::{Duration, NaiveDate};
+use chrono::{Duration, NaiveDate, Utc};
 use grocy::{
-    structs::{Location, ProductDetails},
+    structs::{Location, ProductDetails, ShoppingLocation, Transaction},
     GrocyApi,
 };
 use inquire::{Confirm, CustomType, DateSelect, MultiSelect, Select, Text};
 
-use crate::{dynprompt, error::Error, GrocyConfig};
+use crate::{
+    dynprompt,
+    error::Error,
+    imports::{ImportLog, ImportRecord, ImportStatus, ItemOutcome, ItemRecord, PriceUpdate},
+    DepositPolicy, GrocyConfig,
+};
+
+/// Number of decimal places of the prices sent to Grocy; these are prices per stock unit,
+/// which can be much smaller than the minor unit of the currency (e.g., price per gram)
+const GROCY_PRICE_DECIMAL_PLACES: u32 = 6;
 
 struct GrocyState {
     api: GrocyApi,
     locations: Vec<Location>,
 }
 
-pub(super) fn purchase_lidl_products(
+pub(super) fn purchase_receipt_products(
     config: &mut GrocyConfig,
     receipt: ReceiptDetailed,
 ) -> Result<()> {
+    if !confirm_reimport(&receipt)? {
+        return Err(Error::ReceiptAlreadyImported.into());
+    }
+    if !reconcile_receipt(&receipt)? {
+        return Err(Error::ReceiptNotReconciled.into());
+    }
+    let (products, other_lines) = confirm_other_lines(receipt.product_lines())?;
+
     let grocy_api = init_grocy_api(config)?;
     let store_id = get_store_id(config, &grocy_api, &receipt.store)?;
 
@@ -30,18 +54,66 @@ pub(super) fn purchase_lidl_products(
         locations,
     };
 
-    let skipped_products: Vec<_> = receipt
-        .items
+    let mut savings: BTreeMap<DiscountKind, Money> = BTreeMap::new();
+    for discount in receipt.items.iter().flat_map(|item| &item.discounts) {
+        let total = savings
+            .entry(discount.kind)
+            .or_insert_with(|| Money::zero(discount.amount.currency.clone()));
+        *total = total.checked_add(&discount.amount)?;
+    }
+
+    let mut record = ImportRecord {
+        id: ImportLog::load()?.next_id(),
+        receipt_id: receipt.id.clone(),
+        receipt_date: receipt.date,
+        store: receipt.store.name.clone(),
+        imported_at: Utc::now(),
+        items: vec![],
+        transactions: vec![],
+        created_barcodes: vec![],
+        created_products: vec![],
+        price_updates: vec![],
+        incomplete: true,
+    };
+    ImportLog::save_record(&record)?;
+
+    let skipped_products: Vec<_> = products
         .into_iter()
         .rev() // items scanned first are at the bottom of the bag
-        .filter(|item| {
-            match purchase_lidl_product(
-                &grocy_state,
-                store_id,
-                item,
-                receipt.date.date(),
-                &receipt.currency,
-            ) {
+        .filter(|line| {
+            let result = product_deposit(line, config.deposits, &receipt).and_then(|deposit| {
+                purchase_receipt_product(
+                    &grocy_state,
+                    store_id,
+                    line.product,
+                    &deposit,
+                    receipt.date.date(),
+                    &mut record,
+                )
+            });
+            let (outcome, error) = match &result {
+                Ok(_) => (ItemOutcome::Imported, None),
+                Err(error) if matches!(error.downcast_ref(), Some(Error::SkippedProduct)) => {
+                    (ItemOutcome::Skipped, None)
+                }
+                Err(error) => (ItemOutcome::Failed, Some(error.to_string())),
+            };
+            record.items.push(ItemRecord {
+                name: line.product.name.clone(),
+                barcode: line.product.barcode.clone(),
+                outcome,
+                error,
+            });
+            // saved after each product, so that nothing done in Grocy is left out of the log
+            // if the import is interrupted
+            if let Err(error) = ImportLog::save_record(&record) {
+                println!(
+                    "{}",
+                    format!("ERROR: Could not save the import log: {}", error).red()
+                );
+            }
+
+            match result {
                 Ok(_) => false,
                 Err(error) => {
                     println!("{}", format!("ERROR: {}", error).red());
@@ -49,7 +121,28 @@ pub(super) fn purchase_lidl_products(
                 }
             }
         })
+        .map(|line| line.product)
         .collect();
+    // recorded even if some products failed, so that the receipt is known to be partially
+    // imported and what was imported can be undone
+    record.incomplete = false;
+    ImportLog::save_record(&record)?;
+
+    if !other_lines.is_empty() {
+        println!();
+        println!(
+            "{}",
+            "The following lines are not products and were not added to Grocy:".on_blue()
+        );
+        for item in other_lines {
+            println!(
+                "- {} {} ({})",
+                item.total_price().to_string().bright_blue(),
+                item.name.green(),
+                item.kind
+            );
+        }
+    }
 
     if !skipped_products.is_empty() {
         println!();
@@ -63,9 +156,141 @@ pub(super) fn purchase_lidl_products(
         }
     }
 
+    if !savings.is_empty() {
+        println!();
+        println!("{}", "Savings in this receipt:".on_green());
+        for (kind, amount) in savings {
+            println!("- {}: {}", kind, amount.to_string().bright_green());
+        }
+    }
+
     Ok(())
 }
 
+/// Warn if the receipt was imported before, since importing it again would add its
+/// products to the stock twice. Returns whether the import should go ahead.
+fn confirm_reimport(receipt: &ReceiptDetailed) -> Result<bool> {
+    let status = match ImportLog::load()?.status(&receipt.id) {
+        Some(status) => status,
+        None => return Ok(true),
+    };
+
+    println!(
+        "{}",
+        format!("WARNING: This receipt was already {}!", status)
+            .on_red()
+            .bold()
+    );
+    if status == ImportStatus::PartiallyImported {
+        println!("Products that were imported before will be added to the stock again.");
+    }
+
+    Ok(Confirm::new("Do you want to import this receipt again?")
+        .with_default(false)
+        .with_help_message("Imports can be undone with the undo-import command")
+        .prompt()?)
+}
+
+/// Line of the receipt that is not a product, shown to be imported as a product anyway
+struct OtherLineChoice<'a>(&'a ReceiptItem);
+
+impl Display for OtherLineChoice<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} ({})",
+            self.0.total_price(),
+            self.0.name,
+            self.0.kind
+        )
+    }
+}
+
+/// Show the lines that were not recognized as products, which would not be added to Grocy,
+/// so that lines mistaken for deposits, bags or services can be imported as products.
+/// Returns the products to import and the lines that are left out.
+fn confirm_other_lines<'a>(
+    (mut products, other_lines): (Vec<ProductLine<'a>>, Vec<&'a ReceiptItem>),
+) -> Result<(Vec<ProductLine<'a>>, Vec<&'a ReceiptItem>)> {
+    if other_lines.is_empty() {
+        return Ok((products, other_lines));
+    }
+
+    let options: Vec<_> = other_lines
+        .iter()
+        .map(|item| OtherLineChoice(item))
+        .collect();
+    let selected = MultiSelect::new(
+        "These lines are not products and will not be added to Grocy. Select any that are products:",
+        options,
+    )
+    .with_help_message("Press enter without selecting any line to leave them all out")
+    .prompt()?;
+
+    let selected: Vec<_> = selected.into_iter().map(|choice| choice.0).collect();
+    let mut others = vec![];
+    for item in other_lines {
+        if selected.iter().any(|product| std::ptr::eq(*product, item)) {
+            products.push(ProductLine {
+                product: item,
+                deposits: vec![],
+            });
+        } else {
+            others.push(item);
+        }
+    }
+
+    Ok((products, others))
+}
+
+/// Check the items of the receipt against its printed totals before importing anything,
+/// since a mismatch means that some product or discount was not read correctly.
+/// Returns whether the import should go ahead.
+fn reconcile_receipt(receipt: &ReceiptDetailed) -> Result<bool> {
+    let reconciliation = receipt.reconcile()?;
+    if reconciliation.is_balanced() {
+        return Ok(true);
+    }
+
+    println!(
+        "{}",
+        "WARNING: The products in this receipt do not add up to its totals!"
+            .on_red()
+            .bold()
+    );
+    if let Some(printed_total) = &reconciliation.printed_total {
+        println!(
+            "Total in receipt: {}, sum of products minus discounts: {}",
+            printed_total.to_string().bright_blue(),
+            reconciliation.items_total.to_string().bright_magenta()
+        );
+    }
+    if let Some(printed_discount) = &reconciliation.printed_discount {
+        println!(
+            "Total discount in receipt: {}, sum of discounts: {}",
+            printed_discount.to_string().bright_blue(),
+            reconciliation.items_discount.to_string().bright_magenta()
+        );
+    }
+    println!("Some products, discounts or quantities might be missing or wrong.");
+
+    Ok(Confirm::new("Do you want to import this receipt anyway?")
+        .with_default(false)
+        .prompt()?)
+}
+
+/// Deposit to add to the price of a product, according to the configured policy
+fn product_deposit(
+    line: &ProductLine,
+    policy: DepositPolicy,
+    receipt: &ReceiptDetailed,
+) -> Result<Money> {
+    match policy {
+        DepositPolicy::Fold => line.total_deposit(),
+        DepositPolicy::Skip => Ok(Money::zero(receipt.currency.clone())),
+    }
+}
+
 fn init_grocy_api(config: &mut GrocyConfig) -> Result<GrocyApi> {
     let base_url = match &config.base_url {
         Some(url) => url,
@@ -95,34 +320,134 @@ fn init_grocy_api(config: &mut GrocyConfig) -> Result<GrocyApi> {
 }
 
 fn get_store_id(config: &mut GrocyConfig, grocy_api: &GrocyApi, store: &Store) -> Result<u32> {
-    let available_locations = grocy_api.get_shopping_locations()?;
     match config.stores.get(&store.id) {
         Some(id) => Ok(*id),
         None => {
-            let location = Select::new("Select store for this receipt:", available_locations)
-                .with_help_message(&format!("Store name from receipt: {}", store.name))
+            let location_id = prompt_shopping_location(grocy_api, store)?;
+            config.stores.insert(store.id.clone(), location_id);
+            Ok(location_id)
+        }
+    }
+}
+
+enum ShoppingLocationChoice {
+    Existing(ShoppingLocation),
+    Create,
+}
+
+impl Display for ShoppingLocationChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Existing(location) => location.fmt(f),
+            Self::Create => write!(f, "Create a new shopping location for this store"),
+        }
+    }
+}
+
+/// Ask for the shopping location of Grocy that a store corresponds to, which can also be
+/// created from the name and address of the store. Returns the id of the shopping location.
+fn prompt_shopping_location(grocy_api: &GrocyApi, store: &Store) -> Result<u32> {
+    let mut options: Vec<_> = grocy_api
+        .get_shopping_locations()?
+        .into_iter()
+        .map(ShoppingLocationChoice::Existing)
+        .collect();
+    options.push(ShoppingLocationChoice::Create);
+
+    let choice = Select::new("Select store for this receipt:", options)
+        .with_help_message(&format!("Store from receipt: {}", store))
+        .prompt()?;
+    match choice {
+        ShoppingLocationChoice::Existing(location) => Ok(location.id),
+        ShoppingLocationChoice::Create => {
+            let name = Text::new("Enter name of the new shopping location:")
+                .with_initial_value(&store.name)
                 .prompt()?;
-            config.stores.insert(store.id.clone(), location.id);
-            Ok(location.id)
+            let description = store
+                .address
+                .iter()
+                .cloned()
+                .chain(store.country.clone())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let description = Text::new("Enter description of the new shopping location:")
+                .with_initial_value(&description)
+                .with_help_message("Press ESC to leave it without a description")
+                .prompt_skippable()?
+                .filter(|description| !description.is_empty());
+
+            let location = grocy_api.create_shopping_location(&name, description.as_deref())?;
+            println!("Created shopping location on Grocy: {}", name.bright_cyan());
+            Ok(location.created_object_id.parse()?)
         }
     }
 }
 
-fn purchase_lidl_product(
+/// Print the stores mapped to shopping locations of Grocy
+pub(super) fn list_store_mappings(config: &mut GrocyConfig) -> Result<()> {
+    if config.stores.is_empty() {
+        println!("No stores are mapped to shopping locations of Grocy yet");
+        return Ok(());
+    }
+
+    let grocy_api = init_grocy_api(config)?;
+    let locations = grocy_api.get_shopping_locations()?;
+    let mut stores: Vec<_> = config.stores.iter().collect();
+    stores.sort();
+    for (store_id, location_id) in stores {
+        let location = match locations
+            .iter()
+            .find(|location| location.id == *location_id)
+        {
+            Some(location) => location.name.bright_cyan(),
+            None => "not found in Grocy".red(),
+        };
+        println!("{} -> {} (#{})", store_id.green(), location, location_id);
+    }
+
+    Ok(())
+}
+
+/// Map a store to another shopping location of Grocy, or to a new one
+pub(super) fn edit_store_mapping(config: &mut GrocyConfig, store_id: String) -> Result<()> {
+    let grocy_api = init_grocy_api(config)?;
+    // only the id of the store is known outside of a receipt
+    let store = Store {
+        id: store_id.clone(),
+        name: store_id.clone(),
+        address: vec![],
+        country: None,
+    };
+
+    let location_id = prompt_shopping_location(&grocy_api, &store)?;
+    config.stores.insert(store_id, location_id);
+    Ok(())
+}
+
+/// Remove the mapping of a store, so that its shopping location is asked for again
+/// the next time one of its receipts is imported
+pub(super) fn remove_store_mapping(config: &mut GrocyConfig, store_id: String) -> Result<()> {
+    config
+        .stores
+        .remove(&store_id)
+        .ok_or(Error::StoreNotMapped(store_id.clone()))?;
+    println!("Removed the mapping of store {}", store_id.green());
+    Ok(())
+}
+
+fn purchase_receipt_product(
     grocy_state: &GrocyState,
     store_id: u32,
     product: &ReceiptItem,
+    deposit: &Money,
     purchase_date: NaiveDate,
-    currency: &Currency,
+    record: &mut ImportRecord,
 ) -> Result<()> {
-    let discount: f64 = product
-        .discounts
-        .iter()
-        .map(|discount| discount.amount)
-        .sum();
+    let discount = product.total_discount()?;
 
     // without discounts
-    let total_amount = product.quantity * product.unit_price;
+    let total_amount = product.total_price();
+    let paid_amount = total_amount.checked_sub(&discount)?;
 
     println!();
     println!();
@@ -130,18 +455,29 @@ fn purchase_lidl_product(
         "Handling product {} {} ({})",
         format!("{}x", product.quantity).yellow(),
         product.name.green(),
-        if discount != 0.0 {
+        if !discount.is_zero() {
             format!(
                 "{} = {} - {}",
-                format!("{:.2} {}", total_amount - discount, currency.symbol).bright_blue(),
-                format!("{:.2} {}", total_amount, currency.symbol).bright_magenta(),
-                format!("{:.2} {}", discount, currency.symbol).bright_green(),
+                paid_amount.to_string().bright_blue(),
+                total_amount.to_string().bright_magenta(),
+                discount.to_string().bright_green(),
             )
             .into()
         } else {
-            format!("{:.2} {}", total_amount, currency.symbol).bright_blue()
+            total_amount.to_string().bright_blue()
         }
     );
+    for discount in &product.discounts {
+        println!(
+            "  {} {} ({})",
+            format!("-{}", discount.amount).bright_green(),
+            discount.description.as_deref().unwrap_or("Discount"),
+            discount.kind
+        );
+    }
+    if !deposit.is_zero() {
+        println!("  {} Deposit", format!("+{}", deposit).bright_yellow());
+    }
     let product_details = grocy_state
         .api
         .get_product_by_barcode(&product.barcode)
@@ -152,10 +488,16 @@ fn purchase_lidl_product(
             );
             details
         })
-        .or_else(|_| handle_product_without_known_barcode(&grocy_state.api, product, store_id))?;
+        .or_else(|error| match error.downcast_ref() {
+            // other errors (e.g., Grocy being unreachable) do not mean the barcode is unknown
+            Some(grocy::error::Error::NotFound(_)) => {
+                handle_product_without_known_barcode(&grocy_state.api, product, store_id, record)
+            }
+            _ => Err(error),
+        })?;
 
     if product_details.product.enable_tare_weight_handling {
-        return Err(Error::ProductHasTareWeightHandling)?;
+        return Err(Error::ProductHasTareWeightHandling.into());
     }
 
     let default_date = Some(product_details.product.default_best_before_days)
@@ -166,26 +508,21 @@ fn purchase_lidl_product(
         .product_barcodes
         .iter()
         .find(|barcode| barcode.barcode == product.barcode);
-    let note = product_barcode.and_then(|barcode| barcode.note.as_deref());
-
-    let price = if product.is_weight {
-        let quantity = CustomType::<f64>::new(&format!(
-            "Enter quantity for this product ({} kg):",
-            product.quantity
-        ))
-        .with_help_message(&format!(
-            "Quantity unit: {}",
-            product_details.default_quantity_unit_purchase.name_plural
-        ))
-        .prompt()?;
-        let quantity = quantity * product_details.qu_conversion_factor_purchase_to_stock;
+    let note = stock_entry_note(
+        product_barcode.and_then(|barcode| barcode.note.as_deref()),
+        product,
+    );
+    let note = note.as_deref();
+
+    let price = if product.unit.is_measured() {
+        let quantity = measured_stock_amount(&grocy_state.api, &product_details, product)?;
 
         let due_date = prompt_due_date(None, default_date)?;
         let location = prompt_location(grocy_state, product_details.product.location_id)?;
 
-        let price = (total_amount - discount) / quantity;
+        let price = price_per_unit(&paid_amount.checked_add(deposit)?, quantity)?;
 
-        grocy_state.api.purchase_product(
+        let transactions = grocy_state.api.purchase_product(
             product_details.product.id,
             quantity,
             due_date,
@@ -195,6 +532,7 @@ fn purchase_lidl_product(
             Some(store_id),
             note,
         )?;
+        record_transactions(record, transactions)?;
 
         price
     } else {
@@ -211,7 +549,12 @@ fn purchase_lidl_product(
             None => Err(Error::BarcodeQuantityUnitNotFound)?,
         };
 
-        let quantity = product.quantity.round() as u32;
+        let quantity = product
+            .quantity
+            .round()
+            .to_u32()
+            .filter(|quantity| *quantity > 0)
+            .ok_or(Error::UnsupportedQuantity(product.quantity))?;
 
         let mut last_date = None;
         let due_dates = (1..=quantity)
@@ -223,12 +566,28 @@ fn purchase_lidl_product(
 
         let location = prompt_location(grocy_state, product_details.product.location_id)?;
 
-        let discount_per_item = discount / quantity as f64;
+        // the discount is split between each unit, rounded to the minor unit of the currency;
+        // leftover cents go to the first units, so that the sum of the prices of all units
+        // is exactly the price paid in the receipt
+        // (the same goes for the deposit)
+        let discounts_per_item = discount.split(quantity);
+        let deposits_per_item = deposit.split(quantity);
 
-        let price = (product.unit_price - discount_per_item) / product_barcode_amount;
+        let mut price = 0.;
+        for ((due_date, discount_per_item), deposit_per_item) in due_dates
+            .into_iter()
+            .zip(discounts_per_item)
+            .zip(deposits_per_item)
+        {
+            price = price_per_unit(
+                &product
+                    .unit_price
+                    .checked_sub(&discount_per_item)?
+                    .checked_add(&deposit_per_item)?,
+                product_barcode_amount,
+            )?;
 
-        for due_date in due_dates {
-            grocy_state.api.purchase_product(
+            let transactions = grocy_state.api.purchase_product(
                 product_details.product.id,
                 product_barcode_amount,
                 due_date,
@@ -238,6 +597,7 @@ fn purchase_lidl_product(
                 Some(store_id),
                 note,
             )?;
+            record_transactions(record, transactions)?;
         }
 
         price
@@ -246,14 +606,137 @@ fn purchase_lidl_product(
     if let Some(barcode) = product_barcode {
         grocy_state
             .api
-            .update_barcode_last_price(barcode.id, price)?;
+            .update_barcode_last_price(barcode.id, Some(price))?;
+        // barcodes created by this import are removed when undoing it anyway
+        if !record.created_barcodes.contains(&barcode.id) {
+            record.price_updates.push(PriceUpdate {
+                barcode_id: barcode.id,
+                previous_price: barcode.last_price,
+            });
+            ImportLog::save_record(record)?;
+        }
     }
 
     Ok(())
 }
 
+fn record_transactions(record: &mut ImportRecord, transactions: Vec<Transaction>) -> Result<()> {
+    for transaction in transactions {
+        if !record.transactions.contains(&transaction.transaction_id) {
+            record.transactions.push(transaction.transaction_id);
+        }
+    }
+    ImportLog::save_record(record)
+}
+
+/// Amount of stock units of a product that was measured at the till (e.g., 0.957 kg).
+/// It is converted if a quantity unit of Grocy matches the unit of the receipt, or another
+/// unit of the same kind (e.g., grams instead of kilograms), and it can be converted into
+/// the stock unit; otherwise, it is asked for, since the measured quantity is not a number
+/// of stock units (e.g., 500 g are not 500 pieces).
+fn measured_stock_amount(
+    grocy_api: &GrocyApi,
+    product_details: &ProductDetails,
+    product: &ReceiptItem,
+) -> Result<f64> {
+    let stock_unit_id = product_details.quantity_unit_stock.id;
+    let conversions = grocy_api.get_quantity_unit_conversions(product_details.product.id)?;
+
+    let amount = grocy_api
+        .get_quantity_units()?
+        .into_iter()
+        .find_map(|quantity_unit| {
+            let unit = Unit::from_name(&quantity_unit.name)
+                .or_else(|| Unit::from_name(&quantity_unit.name_plural))?;
+            let amount = (product.quantity * product.unit.factor_to(unit)?).to_f64()?;
+
+            if quantity_unit.id == stock_unit_id {
+                return Some(amount);
+            }
+            conversions
+                .iter()
+                .find(|conversion| {
+                    conversion.from_qu_id == quantity_unit.id
+                        && conversion.to_qu_id == stock_unit_id
+                })
+                .map(|conversion| amount * conversion.factor)
+        });
+
+    match amount {
+        Some(amount) => {
+            println!(
+                "{} {} is {} {}",
+                product.quantity,
+                product.unit,
+                amount,
+                product_details.quantity_unit_stock.name_plural
+            );
+            Ok(amount)
+        }
+        None => {
+            println!(
+                "{}",
+                format!(
+                    "Grocy has no conversion from {} to {} for this product",
+                    product.unit, product_details.quantity_unit_stock.name_plural
+                )
+                .yellow()
+            );
+            let amount = CustomType::<f64>::new(&format!(
+                "Enter quantity for this product ({} {}):",
+                product.quantity, product.unit
+            ))
+            .with_help_message(&format!(
+                "Quantity unit: {}",
+                product_details.default_quantity_unit_purchase.name_plural
+            ))
+            .with_error_message("Please type a valid number (use dot for decimals)")
+            .with_parser(&|input| match input.parse::<f64>() {
+                Ok(amount) if amount > 0. => Ok(amount),
+                _ => Err(()),
+            })
+            .prompt()?;
+            Ok(amount * product_details.qu_conversion_factor_purchase_to_stock)
+        }
+    }
+}
+
+/// Note of the stock entries of a product, which includes the note of the barcode
+/// and the reasons why the product was cheaper, if any
+fn stock_entry_note(barcode_note: Option<&str>, product: &ReceiptItem) -> Option<String> {
+    let discounts = product
+        .discounts
+        .iter()
+        .map(|discount| match &discount.description {
+            Some(description) => format!("{} -{}", description, discount.amount),
+            None => format!("{} discount -{}", discount.kind, discount.amount),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    match (barcode_note, discounts.is_empty()) {
+        (Some(note), false) => Some(format!("{} ({})", note, discounts)),
+        (Some(note), true) => Some(note.to_string()),
+        (None, false) => Some(discounts),
+        (None, true) => None,
+    }
+}
+
+/// Price of each stock unit when buying `amount` stock units for `total`, as sent to Grocy
+fn price_per_unit(total: &Money, amount: f64) -> Result<f64> {
+    let amount = Decimal::from_f64(amount)
+        .filter(|amount| !amount.is_zero())
+        .ok_or(Error::UnsupportedAmount(amount))?;
+
+    Ok((total.amount / amount)
+        .round_dp(GROCY_PRICE_DECIMAL_PLACES)
+        .to_f64()
+        .unwrap_or_default())
+}
+
 enum UnknownProductAction {
     AssociateProduct,
+    CreateProduct,
     Skip,
 }
 
@@ -261,6 +744,7 @@ impl Display for UnknownProductAction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::AssociateProduct => write!(f, "Associate barcode with existing product"),
+            Self::CreateProduct => write!(f, "Create a new product with this barcode"),
             Self::Skip => write!(f, "Skip this product"),
         }
     }
@@ -270,9 +754,11 @@ fn handle_product_without_known_barcode(
     grocy_api: &GrocyApi,
     product: &ReceiptItem,
     store_id: u32,
+    record: &mut ImportRecord,
 ) -> Result<ProductDetails> {
     let options = vec![
         UnknownProductAction::AssociateProduct,
+        UnknownProductAction::CreateProduct,
         UnknownProductAction::Skip,
     ];
 
@@ -294,46 +780,316 @@ fn handle_product_without_known_barcode(
                     .prompt_skippable()?
                     .ok_or(Error::SkippedProduct)?;
 
-            let mut quantity = None;
-            if !product.is_weight {
-                let quantity_unit = if let Some(qu_id) = selected_product.qu_id_purchase {
-                    grocy_api.get_quantity_unit(qu_id)?
-                } else {
-                    println!("The selected product does not define a purchase quantity unit!");
-                    println!("Let's try again:");
-                    return handle_product_without_known_barcode(grocy_api, product, store_id);
-                };
-
-                quantity = Some(
-                    CustomType::<f64>::new("Enter quantity for each barcode:")
-                        .with_help_message(&format!("Quantity unit: {}", quantity_unit.name_plural))
-                        .with_error_message("Please type a valid number (use dot for decimals)")
-                        .prompt()?,
-                );
+            if !product.unit.is_measured() && selected_product.qu_id_purchase.is_none() {
+                println!("The selected product does not define a purchase quantity unit!");
+                println!("Let's try again:");
+                return handle_product_without_known_barcode(grocy_api, product, store_id, record);
             }
 
-            let note = Text::new("Enter note for this barcode:")
-                .with_initial_value(&product.name)
-                .prompt_skippable()?;
-
-            let userfields = prompt_barcode_userfields(grocy_api)?;
-
-            grocy_api.create_product_barcode(
+            add_barcode_to_product(
+                grocy_api,
+                product,
+                store_id,
+                record,
                 selected_product.id,
-                &product.barcode,
-                quantity,
                 selected_product.qu_id_purchase,
-                store_id,
-                note.as_deref(),
-                userfields,
-            )?;
+            )
+        }
+        UnknownProductAction::CreateProduct => {
+            let (product_id, qu_id_purchase) = create_product(grocy_api, product, record)?;
 
-            grocy_api.get_product_by_barcode(&product.barcode)
+            add_barcode_to_product(
+                grocy_api,
+                product,
+                store_id,
+                record,
+                product_id,
+                Some(qu_id_purchase),
+            )
         }
         UnknownProductAction::Skip => Err(Error::SkippedProduct.into()),
     }
 }
 
+/// Create a product in Grocy for an item of the receipt, asking for its details.
+/// Returns the ids of the product and of its purchase quantity unit.
+fn create_product(
+    grocy_api: &GrocyApi,
+    product: &ReceiptItem,
+    record: &mut ImportRecord,
+) -> Result<(u32, u32)> {
+    let name = Text::new("Enter name of the new product:")
+        .with_initial_value(&product.name)
+        .prompt()?;
+
+    let product_group = Select::new("Select product group:", grocy_api.get_product_groups()?)
+        .with_help_message("Press ESC to leave the product without a group")
+        .prompt_skippable()?;
+    let location = Select::new("Select default location:", grocy_api.get_locations()?).prompt()?;
+
+    let quantity_units = grocy_api.get_quantity_units()?;
+    // products sold by weight or volume are usually stocked in the unit of the receipt
+    let receipt_unit_position = quantity_units
+        .iter()
+        .position(|quantity_unit| {
+            product.unit.is_measured()
+                && [&quantity_unit.name, &quantity_unit.name_plural]
+                    .into_iter()
+                    .any(|name| Unit::from_name(name) == Some(product.unit))
+        })
+        .unwrap_or(0);
+    let stock_unit = Select::new("Select stock quantity unit:", quantity_units.clone())
+        .with_starting_cursor(receipt_unit_position)
+        .prompt()?;
+    let stock_unit_position = quantity_units
+        .iter()
+        .position(|quantity_unit| quantity_unit.id == stock_unit.id)
+        .unwrap_or(0);
+    let purchase_unit = Select::new("Select purchase quantity unit:", quantity_units)
+        .with_starting_cursor(stock_unit_position)
+        .prompt()?;
+
+    let default_best_before_days = CustomType::<i32>::new("Enter default due days:")
+        .with_help_message("0 for no default due date, or -1 if the product never expires")
+        .with_error_message("Please type a valid whole number")
+        .with_default(0)
+        .prompt()?;
+
+    let factor = if purchase_unit.id != stock_unit.id {
+        Some(
+            CustomType::<f64>::new(&format!(
+                "How many {} are there in 1 {}?",
+                stock_unit.name_plural, purchase_unit.name
+            ))
+            .with_error_message("Please type a valid number (use dot for decimals)")
+            .prompt()?,
+        )
+    } else {
+        None
+    };
+
+    let product_id: u32 = grocy_api
+        .create_product(
+            &name,
+            product_group.map(|group| group.id),
+            location.id,
+            stock_unit.id,
+            purchase_unit.id,
+            default_best_before_days,
+        )?
+        .created_object_id
+        .parse()?;
+    record.created_products.push(product_id);
+    ImportLog::save_record(record)?;
+
+    if let Some(factor) = factor {
+        grocy_api.create_quantity_unit_conversion(
+            product_id,
+            purchase_unit.id,
+            stock_unit.id,
+            factor,
+        )?;
+    }
+    println!("Created product on Grocy: {}", name.bright_cyan());
+
+    Ok((product_id, purchase_unit.id))
+}
+
+/// Associate the barcode of an item of the receipt with a product of Grocy, asking for
+/// the details of the barcode, and get the product
+fn add_barcode_to_product(
+    grocy_api: &GrocyApi,
+    product: &ReceiptItem,
+    store_id: u32,
+    record: &mut ImportRecord,
+    product_id: u32,
+    qu_id_purchase: Option<u32>,
+) -> Result<ProductDetails> {
+    let mut quantity = None;
+    if let (false, Some(qu_id)) = (product.unit.is_measured(), qu_id_purchase) {
+        let quantity_unit = grocy_api.get_quantity_unit(qu_id)?;
+
+        quantity = Some(
+            CustomType::<f64>::new("Enter quantity for each barcode:")
+                .with_help_message(&format!("Quantity unit: {}", quantity_unit.name_plural))
+                .with_error_message("Please type a valid number (use dot for decimals)")
+                .prompt()?,
+        );
+    }
+
+    let note = Text::new("Enter note for this barcode:")
+        .with_initial_value(&product.name)
+        .prompt_skippable()?;
+
+    let userfields = prompt_barcode_userfields(grocy_api)?;
+
+    let barcode = grocy_api.create_product_barcode(
+        product_id,
+        &product.barcode,
+        quantity,
+        qu_id_purchase,
+        store_id,
+        note.as_deref(),
+        userfields,
+    )?;
+    record
+        .created_barcodes
+        .push(barcode.created_object_id.parse()?);
+    ImportLog::save_record(record)?;
+
+    grocy_api.get_product_by_barcode(&product.barcode)
+}
+
+/// Undo an import recorded in the import log: its purchases are undone, the barcodes and
+/// products it created are removed and the last prices it updated are restored. Products
+/// that are still in use are only removed if confirmed. If `receipt_id` is not given, the
+/// import to undo is selected from the log.
+pub(super) fn undo_import(config: &mut GrocyConfig, receipt_id: Option<String>) -> Result<()> {
+    let mut log = ImportLog::load()?;
+    if log.imports.is_empty() {
+        return Err(Error::NoImportsFound.into());
+    }
+
+    let position = match receipt_id {
+        Some(receipt_id) => log
+            .imports
+            .iter()
+            .rposition(|record| record.receipt_id == receipt_id)
+            .ok_or(Error::ImportNotFound(receipt_id))?,
+        None => {
+            let options: Vec<_> = log.imports.iter().rev().collect();
+            let choice = Select::new("Select import to undo:", options).raw_prompt()?;
+            log.imports.len() - 1 - choice.index
+        }
+    };
+    let record = log.imports.remove(position);
+
+    let confirmed = Confirm::new(&format!(
+        "Undo {} purchase(s), remove {} barcode(s) and {} product(s), and restore {} last \
+         price(s) in Grocy?",
+        record.transactions.len(),
+        record.created_barcodes.len(),
+        record.created_products.len(),
+        record.price_updates.len()
+    ))
+    .with_default(false)
+    .prompt()?;
+    if !confirmed {
+        return Ok(());
+    }
+
+    let grocy_api = init_grocy_api(config)?;
+    // what could not be undone is kept in the log, to try again later
+    let mut left = ImportRecord {
+        transactions: vec![],
+        created_barcodes: vec![],
+        created_products: vec![],
+        price_updates: vec![],
+        ..record.clone()
+    };
+    let report = |result: Result<()>, what: String| match result {
+        Ok(()) => {
+            println!("Undone {}", what);
+            true
+        }
+        Err(error) => {
+            println!(
+                "{}",
+                format!("ERROR: Could not undo {}: {}", what, error).red()
+            );
+            false
+        }
+    };
+
+    // newest changes are undone first, so that everything goes back to how it was
+    for transaction in record.transactions.into_iter().rev() {
+        let result = grocy_api.undo_transaction(&transaction);
+        if !report(result, format!("purchase {}", transaction)) {
+            left.transactions.insert(0, transaction);
+        }
+    }
+    for update in record.price_updates.into_iter().rev() {
+        let result = grocy_api.update_barcode_last_price(update.barcode_id, update.previous_price);
+        if !report(
+            result,
+            format!("last price of barcode #{}", update.barcode_id),
+        ) {
+            left.price_updates.insert(0, update);
+        }
+    }
+    for &barcode_id in record.created_barcodes.iter().rev() {
+        let result = grocy_api.delete_product_barcode(barcode_id);
+        if !report(result, format!("creation of barcode #{}", barcode_id)) {
+            left.created_barcodes.insert(0, barcode_id);
+        }
+    }
+    // products are removed last, once nothing that was imported refers to them
+    for product_id in record.created_products.into_iter().rev() {
+        let result =
+            match confirm_product_deletion(&grocy_api, product_id, &record.created_barcodes) {
+                Ok(false) => {
+                    println!("Kept product #{}", product_id);
+                    continue;
+                }
+                Ok(true) => grocy_api.delete_product(product_id),
+                Err(error) => Err(error),
+            };
+        if !report(result, format!("creation of product #{}", product_id)) {
+            left.created_products.insert(0, product_id);
+        }
+    }
+
+    let left_count = left.transactions.len()
+        + left.created_barcodes.len()
+        + left.created_products.len()
+        + left.price_updates.len();
+    if !left.is_empty() {
+        log.imports.insert(position, left);
+    }
+    log.save()?;
+
+    if left_count > 0 {
+        return Err(Error::UndoIncomplete(left_count).into());
+    }
+    println!("{}", "The import was undone".green());
+    Ok(())
+}
+
+/// Whether a product created by an import can be deleted, asking for confirmation if it is
+/// still in stock or has barcodes that were not created by the import, which would be lost
+fn confirm_product_deletion(
+    grocy_api: &GrocyApi,
+    product_id: u32,
+    created_barcodes: &[u32],
+) -> Result<bool> {
+    let stock_entries = grocy_api.get_product_stock_entries(product_id)?;
+    let other_barcodes = grocy_api
+        .get_product_barcodes(product_id)?
+        .into_iter()
+        .filter(|barcode| !created_barcodes.contains(&barcode.id))
+        .count();
+    if stock_entries.is_empty() && other_barcodes == 0 {
+        return Ok(true);
+    }
+
+    println!(
+        "{}",
+        format!(
+            "WARNING: Product #{} has {} stock entry(ies) and {} barcode(s) that were not \
+             created by the import, which would be deleted with it",
+            product_id,
+            stock_entries.len(),
+            other_barcodes
+        )
+        .yellow()
+    );
+    Ok(
+        Confirm::new(&format!("Delete product #{} anyway?", product_id))
+            .with_default(false)
+            .prompt()?,
+    )
+}
+
 fn prompt_due_date(
     progress: Option<(u32, u32)>,
     default_date: Option<NaiveDate>,
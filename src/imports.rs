@@ -0,0 +1,198 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, ErrorKind, Write},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::CONFIG_NAME;
+
+/// Name of the file, next to the configuration, where imports are recorded
+const IMPORTS_FILE_NAME: &str = "imports.json";
+
+/// An import of a receipt into Grocy: what happened to each of its products, so that
+/// receipts are not imported twice by mistake, and what it changed in Grocy, so that
+/// it can be undone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct ImportRecord {
+    /// Number of the import in the log, which is unique even if a receipt is imported
+    /// more than once
+    pub id: u32,
+    pub receipt_id: String,
+    pub receipt_date: NaiveDateTime,
+    pub store: String,
+    pub imported_at: DateTime<Utc>,
+    #[serde(default)]
+    pub items: Vec<ItemRecord>,
+    /// Ids of the stock transactions of the purchases, in the order they were made
+    #[serde(default)]
+    pub transactions: Vec<String>,
+    /// Ids of the product barcodes that were created
+    #[serde(default)]
+    pub created_barcodes: Vec<u32>,
+    /// Ids of the products that were created
+    #[serde(default)]
+    pub created_products: Vec<u32>,
+    /// Last prices of existing product barcodes before they were updated
+    #[serde(default)]
+    pub price_updates: Vec<PriceUpdate>,
+    /// Whether the import did not go through all the products of the receipt
+    /// (e.g., because it was interrupted)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub incomplete: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct PriceUpdate {
+    pub barcode_id: u32,
+    pub previous_price: Option<f64>,
+}
+
+/// Outcome of the import of a product of the receipt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct ItemRecord {
+    pub name: String,
+    pub barcode: String,
+    pub outcome: ItemOutcome,
+    /// Why the product could not be imported, if it failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum ItemOutcome {
+    Imported,
+    Skipped,
+    Failed,
+}
+
+/// Whether a receipt was imported before
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ImportStatus {
+    Imported,
+    /// Some products were skipped or failed to be imported
+    PartiallyImported,
+}
+
+impl std::fmt::Display for ImportStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Imported => write!(f, "imported"),
+            Self::PartiallyImported => write!(f, "partially imported"),
+        }
+    }
+}
+
+impl ImportRecord {
+    /// Whether undoing the import would change nothing in Grocy
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+            && self.created_barcodes.is_empty()
+            && self.created_products.is_empty()
+            && self.price_updates.is_empty()
+    }
+
+    pub fn status(&self) -> ImportStatus {
+        if !self.incomplete
+            && self
+                .items
+                .iter()
+                .all(|item| item.outcome == ItemOutcome::Imported)
+        {
+            ImportStatus::Imported
+        } else {
+            ImportStatus::PartiallyImported
+        }
+    }
+}
+
+impl std::fmt::Display for ImportRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "#{} {} - {} ({}), {} on {} - {} purchase(s)",
+            self.id,
+            self.receipt_date.format("%a %b %e %Y %T"),
+            self.store,
+            self.receipt_id,
+            self.status(),
+            self.imported_at.format("%Y-%m-%d %H:%M"),
+            self.transactions.len()
+        )
+    }
+}
+
+/// Imports made into Grocy, from oldest to newest
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct ImportLog {
+    pub imports: Vec<ImportRecord>,
+}
+
+impl ImportLog {
+    pub fn load() -> Result<Self> {
+        match File::open(log_path()?) {
+            Ok(file) => Ok(serde_json::from_reader(BufReader::new(file))?),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = log_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // written to another file first, so that the log is not lost if writing it
+        // is interrupted
+        let temp_path = path.with_extension("json.tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(temp_path, path)?;
+        Ok(())
+    }
+
+    /// Whether a receipt was imported before; a receipt imported again after being
+    /// partially imported counts as imported if any of its imports was complete
+    pub fn status(&self, receipt_id: &str) -> Option<ImportStatus> {
+        self.imports
+            .iter()
+            .filter(|record| record.receipt_id == receipt_id)
+            .map(ImportRecord::status)
+            .min_by_key(|status| *status != ImportStatus::Imported)
+    }
+
+    /// Id for a new import, after those of all the imports in the log
+    pub fn next_id(&self) -> u32 {
+        self.imports
+            .iter()
+            .map(|record| record.id + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Add an import to the log, or update it if it is already there
+    pub fn save_record(record: &ImportRecord) -> Result<()> {
+        let mut log = Self::load()?;
+        let existing = log
+            .imports
+            .iter_mut()
+            .find(|existing| existing.id == record.id);
+        match existing {
+            Some(existing) => *existing = record.clone(),
+            None => log.imports.push(record.clone()),
+        }
+        log.save()
+    }
+}
+
+fn log_path() -> Result<PathBuf> {
+    Ok(
+        confy::get_configuration_file_path(CONFIG_NAME, Some(CONFIG_NAME))?
+            .with_file_name(IMPORTS_FILE_NAME),
+    )
+}
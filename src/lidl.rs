@@ -1,18 +1,132 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
 use anyhow::Result;
-use inquire::{Confirm, Select, Text};
-use ir::{ReceiptDetailed, StoreApi};
-use lidl::{get_countries, structs::Country, LidlApi, OAuthFlow};
+use chrono::{Duration, NaiveDate, NaiveTime, Utc};
+use colored::Colorize;
+use inquire::{Confirm, CustomType, DateSelect, Select, Text};
+use ir::{
+    Currency, ItemKind, Money, ReceiptDetailed, ReceiptFilter, ReceiptItem, Store, StoreApi, Unit,
+};
+use lidl::{
+    anonymize::{anonymize_html_receipt, anonymize_json_receipt},
+    get_countries,
+    html_receipt::{parse_html_receipt_lenient, LenientReceipt},
+    structs::Country,
+    Endpoints, LidlApi, OAuthFlow, ResponseArchive, RetryPolicy,
+};
+use rust_decimal::Decimal;
+
+use crate::{
+    error::Error, store::prompt_receipt, CachedAccessToken, CachedCountries, LidlConfig, LidlLocale,
+};
+
+/// Minutes before it expires that an access token is no longer used, so that it does not
+/// expire in the middle of a run
+const ACCESS_TOKEN_EXPIRY_MARGIN_MINUTES: i64 = 5;
+/// Days that the countries where Lidl Plus is available are kept before being fetched again
+const COUNTRIES_TTL_DAYS: i64 = 7;
+
+/// Select a receipt from Lidl Plus and fetch it; with `with_html`, it is completed with
+/// its HTML receipt, which takes another request
+pub(super) fn fetch_receipt_from_lidl(
+    config: &mut LidlConfig,
+    filter: ReceiptFilter,
+    archive: Option<PathBuf>,
+    with_html: bool,
+) -> Result<ReceiptDetailed> {
+    let endpoints = config.endpoints.clone().unwrap_or_default();
+    let mut session = LidlSession::log_in(config, endpoints, archive)?;
+
+    let receipt = session.request(config, |lidl_api| prompt_receipt(lidl_api, filter.clone()))?;
+    session.request(config, |lidl_api| {
+        if with_html {
+            lidl_api.get_specific_receipt_with_html(&receipt)
+        } else {
+            lidl_api.get_specific_receipt(&receipt)
+        }
+    })
+}
+
+/// Client of the Lidl Plus API, which logs in again once if the access token of a previous
+/// run is not accepted (e.g., because it was revoked before expiring)
+struct LidlSession {
+    api: LidlApi,
+    endpoints: Endpoints,
+    archive: Option<PathBuf>,
+    uses_cached_token: bool,
+}
+
+impl LidlSession {
+    fn log_in(
+        config: &mut LidlConfig,
+        endpoints: Endpoints,
+        archive: Option<PathBuf>,
+    ) -> Result<Self> {
+        let uses_cached_token =
+            config.refresh_token.is_some() && cached_access_token(config).is_some();
+        let api = login_lidl(config, &endpoints, archive.as_ref())?;
+
+        Ok(Self {
+            api,
+            endpoints,
+            archive,
+            uses_cached_token,
+        })
+    }
+
+    /// Make a request; if Lidl does not accept the cached access token, log in again (with
+    /// the refresh token, or otherwise from scratch) and make the request once more
+    fn request<T>(
+        &mut self,
+        config: &mut LidlConfig,
+        request: impl Fn(&LidlApi) -> Result<T>,
+    ) -> Result<T> {
+        let result = match request(&self.api) {
+            Err(error) if is_unauthorized(&error) && self.uses_cached_token => {
+                println!(
+                    "{}",
+                    "Lidl did not accept the saved access token, getting a new one".yellow()
+                );
+                config.access_token = None;
+                self.uses_cached_token = false;
+                self.api = login_lidl(config, &self.endpoints, self.archive.as_ref())?;
+                request(&self.api)
+            }
+            result => result,
+        };
+        if matches!(&result, Err(error) if is_unauthorized(error)) {
+            // the access token was revoked before expiring, so a new one is needed next time
+            config.access_token = None;
+        }
+        result
+    }
+}
 
-use crate::{error::Error, LidlConfig, LidlLocale};
+/// Access token of a previous run, if it is still valid for a while
+fn cached_access_token(config: &LidlConfig) -> Option<&CachedAccessToken> {
+    config.access_token.as_ref().filter(|access_token| {
+        access_token.expires_at > Utc::now() + Duration::minutes(ACCESS_TOKEN_EXPIRY_MARGIN_MINUTES)
+    })
+}
 
-pub(super) fn fetch_receipt_from_lidl(config: &mut LidlConfig) -> Result<ReceiptDetailed> {
-    let lidl_api = match &config.refresh_token {
-        None => init_token_lidl(config)?,
+/// Get a client of the Lidl Plus API, with the access token of a previous run if it is still
+/// valid, or otherwise with a new one from the refresh token or by logging in
+fn login_lidl(
+    config: &mut LidlConfig,
+    endpoints: &Endpoints,
+    archive: Option<&PathBuf>,
+) -> Result<LidlApi> {
+    let lidl_api = match config.refresh_token.clone() {
+        None => init_token_lidl(config, endpoints)?,
         Some(refresh_token) => {
             let locale = match &config.locale {
                 Some(locale) => locale,
                 None => {
-                    let country = prompt_lidl_country()?;
+                    let country = prompt_lidl_country(config, endpoints)?;
                     let language = country
                         .get_default_language()
                         .ok_or(Error::LidlNoDefaultLanguageForCountry)?;
@@ -24,24 +138,218 @@ pub(super) fn fetch_receipt_from_lidl(config: &mut LidlConfig) -> Result<Receipt
                     config.locale.as_ref().unwrap()
                 }
             };
-            OAuthFlow::get_token_from_refresh_token(
-                locale.country.clone(),
-                locale.language.clone(),
-                refresh_token.clone(),
-            )?
+            // skip the token endpoint while the access token of a previous run is valid
+            let lidl_api = match cached_access_token(config) {
+                Some(access_token) => LidlApi::from_access_token(
+                    endpoints.clone(),
+                    locale.country.clone(),
+                    locale.language.clone(),
+                    access_token.token.clone(),
+                    Some(access_token.expires_at),
+                    refresh_token,
+                ),
+                None => OAuthFlow::get_token_from_refresh_token(
+                    endpoints,
+                    locale.country.clone(),
+                    locale.language.clone(),
+                    refresh_token,
+                    &RetryPolicy::default(),
+                ),
+            };
+            match lidl_api {
+                Err(error) if is_unauthorized(&error) => {
+                    println!(
+                        "{}",
+                        "Your Lidl session has expired or was revoked, please log in again"
+                            .yellow()
+                    );
+                    config.refresh_token = None;
+                    config.access_token = None;
+                    init_token_lidl(config, endpoints)?
+                }
+                lidl_api => lidl_api?,
+            }
         }
     };
-    // Save refresh token to config, for future runs
+    // Save tokens to config, for future runs
     config.refresh_token = Some(lidl_api.get_refresh_token());
+    config.access_token = lidl_api
+        .get_access_token_expiry()
+        .map(|expires_at| CachedAccessToken {
+            token: lidl_api.get_access_token(),
+            expires_at,
+        });
+
+    Ok(match archive {
+        Some(archive) => lidl_api.with_archive(ResponseArchive::new(archive)),
+        None => lidl_api,
+    })
+}
+
+/// Whether Lidl did not accept the credentials, so that the user must log in again
+fn is_unauthorized(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref(), Some(lidl::error::Error::Unauthorized))
+}
+
+/// Read a receipt saved as HTML, asking for the metadata that is not in the HTML itself
+pub(super) fn read_html_receipt(
+    file: &Path,
+    id: Option<String>,
+    date: Option<NaiveDate>,
+    store_id: Option<String>,
+    store_name: Option<String>,
+) -> Result<ReceiptDetailed> {
+    let html = fs::read_to_string(file)?;
+
+    let id = match id {
+        Some(id) => id,
+        None => file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    };
+    let date = match date {
+        Some(date) => date,
+        None => DateSelect::new("What is the date of the receipt?")
+            .with_max_date(chrono::Local::now().date_naive())
+            .prompt()?,
+    };
+    let store_id = match store_id {
+        Some(store_id) => store_id,
+        None => Text::new("What is the id of the store of the receipt?")
+            .with_help_message("Store mappings to Grocy are saved by this id")
+            .prompt()?,
+    };
+    let store_name = store_name.unwrap_or_else(|| store_id.clone());
+
+    let parsed = parse_html_receipt_lenient(
+        id,
+        date.and_time(NaiveTime::MIN),
+        Store {
+            id: store_id,
+            name: store_name,
+            address: vec![],
+            country: None,
+        },
+        &html,
+    )?;
+    resolve_parse_warnings(parsed)
+}
+
+enum UnparsedLineAction {
+    EnterItem,
+    Skip,
+}
+
+impl Display for UnparsedLineAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EnterItem => write!(f, "Enter the item of this line by hand"),
+            Self::Skip => write!(f, "Skip this line"),
+        }
+    }
+}
+
+/// Show the lines of the receipt that could not be parsed, and let the user either enter
+/// the item of each line by hand or leave it out of the receipt
+fn resolve_parse_warnings(parsed: LenientReceipt) -> Result<ReceiptDetailed> {
+    let LenientReceipt {
+        mut receipt,
+        warnings,
+    } = parsed;
+    if warnings.is_empty() {
+        return Ok(receipt);
+    }
+
+    println!(
+        "{}",
+        format!(
+            "{} line(s) of the receipt could not be parsed",
+            warnings.len()
+        )
+        .yellow()
+    );
+
+    // items entered by hand move the position of the following lines
+    let mut inserted = 0;
+    for warning in warnings {
+        println!();
+        println!("{}: {}", warning.line_id.bold(), warning.error);
+        println!("  {}", warning.text.trim().bright_white());
+
+        let options = vec![UnparsedLineAction::EnterItem, UnparsedLineAction::Skip];
+        let action = Select::new("What do you want to do with this line?", options).prompt()?;
+        if let UnparsedLineAction::EnterItem = action {
+            let item = prompt_receipt_item(&warning.text, &receipt.currency)?;
+            receipt.items.insert(warning.position + inserted, item);
+            inserted += 1;
+        }
+    }
+    println!();
+
+    Ok(receipt)
+}
+
+fn prompt_receipt_item(text: &str, currency: &Currency) -> Result<ReceiptItem> {
+    let name = Text::new("What is the name of the item?")
+        .with_initial_value(text.trim())
+        .prompt()?;
+    let barcode = Text::new("What is the barcode of the item?")
+        .with_help_message("For Lidl products, this is lidl-<article id> (e.g., lidl-0082388)")
+        .prompt()?;
+    let unit_price = CustomType::<Decimal>::new("What is the unit price of the item?")
+        .with_help_message(&format!("In {}, and negative for returns", currency.id))
+        .with_error_message("Please type a valid number (use dot for decimals)")
+        .prompt()?;
+    let quantity = CustomType::<Decimal>::new("What is the quantity of the item?")
+        .with_help_message("The weight or volume, if the item is not sold by the piece")
+        .with_error_message("Please type a valid number (use dot for decimals)")
+        .with_default(Decimal::ONE)
+        .prompt()?;
+    let unit = Select::new("What is the unit of the quantity?", Unit::ALL.to_vec())
+        .with_starting_cursor(if quantity.fract().is_zero() { 0 } else { 1 })
+        .prompt()?;
 
-    let receipts = lidl_api.get_available_receipts()?;
+    Ok(ReceiptItem {
+        kind: ItemKind::from_description(&name, unit_price.is_sign_negative()),
+        unit_price: Money::new(unit_price, currency.clone()),
+        quantity,
+        unit,
+        name,
+        barcode,
+        discounts: vec![],
+        tax: None,
+        sources: None,
+    })
+}
 
-    let receipt = Select::new("Select receipt to import:", receipts).prompt()?;
-    lidl_api.get_specific_receipt(&receipt)
+/// Remove the personal data from a Lidl receipt saved as HTML or JSON (from the API),
+/// so that it can be shared; the result is written to `output`, or printed if not given
+pub(super) fn anonymize_receipt_file(file: &Path, output: Option<&Path>) -> Result<()> {
+    let receipt = fs::read_to_string(file)?;
+    let anonymized = if receipt.trim_start().starts_with('{') {
+        anonymize_json_receipt(&receipt)?
+    } else {
+        anonymize_html_receipt(&receipt)
+    };
+
+    match output {
+        Some(output) => {
+            fs::write(output, anonymized)?;
+            println!("Anonymized receipt saved to {}", output.display());
+            println!(
+                "{}",
+                "Please check that it has no personal data left before sharing it".yellow()
+            );
+        }
+        None => print!("{}", anonymized),
+    }
+
+    Ok(())
 }
 
-fn init_token_lidl(config: &mut LidlConfig) -> Result<LidlApi> {
-    let selected_country = prompt_lidl_country()?;
+fn init_token_lidl(config: &mut LidlConfig, endpoints: &Endpoints) -> Result<LidlApi> {
+    let selected_country = prompt_lidl_country(config, endpoints)?;
     let selected_language = selected_country
         .get_default_language()
         .ok_or(Error::LidlNoDefaultLanguageForCountry)?;
@@ -67,7 +375,7 @@ fn init_token_lidl(config: &mut LidlConfig) -> Result<LidlApi> {
         }
     }
 
-    let oauth_flow = OAuthFlow::init(&selected_country, &selected_language)?;
+    let oauth_flow = OAuthFlow::init(endpoints, &selected_country, &selected_language)?;
     println!(
         "Open the following URL in your browser to login: {}",
         oauth_flow.get_url()
@@ -86,8 +394,43 @@ fn init_token_lidl(config: &mut LidlConfig) -> Result<LidlApi> {
     oauth_flow.validate(&callback_url)
 }
 
-fn prompt_lidl_country() -> Result<Country> {
-    let countries = get_countries()?;
+fn prompt_lidl_country(config: &mut LidlConfig, endpoints: &Endpoints) -> Result<Country> {
+    let countries = lidl_countries(config, endpoints)?;
 
     Ok(Select::new("Select country for Lidl:", countries).prompt()?)
 }
+
+/// Countries where Lidl Plus is available, from the configuration if they were fetched
+/// recently or if Lidl cannot be reached now
+fn lidl_countries(config: &mut LidlConfig, endpoints: &Endpoints) -> Result<Vec<Country>> {
+    if let Some(cached) = &config.countries {
+        if cached.fetched_at + Duration::days(COUNTRIES_TTL_DAYS) > Utc::now() {
+            return Ok(cached.countries.clone());
+        }
+    }
+
+    match get_countries(endpoints, &RetryPolicy::default()) {
+        Ok(countries) => {
+            config.countries = Some(CachedCountries {
+                fetched_at: Utc::now(),
+                countries: countries.clone(),
+            });
+            Ok(countries)
+        }
+        Err(error) => match &config.countries {
+            Some(cached) => {
+                println!(
+                    "{}",
+                    format!(
+                        "Could not fetch the countries from Lidl ({}), using the ones from {}",
+                        error,
+                        cached.fetched_at.format("%Y-%m-%d")
+                    )
+                    .yellow()
+                );
+                Ok(cached.countries.clone())
+            }
+            None => Err(error),
+        },
+    }
+}
@@ -1,15 +1,29 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fs::File, io::BufReader, path::PathBuf};
 
+use ::lidl::{structs::Country, Endpoints, ResponseArchive};
 use anyhow::Result;
-use grocy::purchase_lidl_products;
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand};
+use csv_store::CsvStore;
+use grocy::{
+    edit_store_mapping, list_store_mappings, purchase_receipt_products, remove_store_mapping,
+    undo_import,
+};
+use ir::{Currency, ReceiptFilter, StoreApi};
 use serde::{Deserialize, Serialize};
 
-use crate::lidl::fetch_receipt_from_lidl;
+use crate::{
+    error::Error,
+    lidl::{anonymize_receipt_file, fetch_receipt_from_lidl, read_html_receipt},
+    store::prompt_receipt,
+};
 
 mod dynprompt;
 mod error;
 mod grocy;
+mod imports;
 mod lidl;
+mod store;
 
 const CONFIG_NAME: &str = "lidl-to-grocy";
 
@@ -23,6 +37,18 @@ struct Config {
 struct LidlConfig {
     refresh_token: Option<String>,
     locale: Option<LidlLocale>,
+    /// Base URLs of the Lidl Plus services, if not the official ones
+    #[serde(default)]
+    endpoints: Option<Endpoints>,
+    /// Directory where the raw responses of Lidl are archived, if they should be
+    #[serde(default)]
+    archive: Option<PathBuf>,
+    /// Access token from a previous run, used instead of the refresh token until it expires
+    #[serde(default)]
+    access_token: Option<CachedAccessToken>,
+    /// Countries where Lidl Plus is available, so that they are not fetched on every login
+    #[serde(default)]
+    countries: Option<CachedCountries>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -31,20 +57,246 @@ struct LidlLocale {
     language: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedAccessToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedCountries {
+    fetched_at: DateTime<Utc>,
+    countries: Vec<Country>,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 struct GrocyConfig {
     base_url: Option<String>,
     api_key: Option<String>,
     stores: HashMap<String, u32>,
+    #[serde(default)]
+    deposits: DepositPolicy,
+}
+
+/// What to do with the deposits paid for the containers of products (e.g., bottles)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum DepositPolicy {
+    /// Add the deposit to the price of the product it was paid for
+    #[default]
+    Fold,
+    /// Leave the deposit out of the price of the product, since it is refunded later
+    Skip,
+}
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    filter: ReceiptFilterArgs,
+    /// Archive the raw responses of Lidl in this directory, or read them from it
+    /// when replaying (defaults to `archive` in the `[lidl]` configuration)
+    #[arg(long, global = true)]
+    archive: Option<PathBuf>,
+}
+
+/// Filters for the receipts available to select
+#[derive(Args)]
+struct ReceiptFilterArgs {
+    /// Only list receipts from this date onwards (YYYY-MM-DD)
+    #[arg(long, global = true)]
+    from: Option<NaiveDate>,
+    /// Only list receipts up to this date (YYYY-MM-DD)
+    #[arg(long, global = true)]
+    to: Option<NaiveDate>,
+    /// Only list receipts from the store with this id
+    #[arg(long, global = true)]
+    store: Option<String>,
+}
+
+impl From<ReceiptFilterArgs> for ReceiptFilter {
+    fn from(value: ReceiptFilterArgs) -> Self {
+        Self {
+            from: value.from,
+            to: value.to,
+            store_id: value.store,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fetch a receipt from Lidl and save it to a file, instead of importing it into Grocy
+    Export {
+        /// Path of the receipt file to write
+        file: PathBuf,
+    },
+    /// Import a receipt from a file into Grocy, instead of fetching it from Lidl
+    Import {
+        /// Path of the receipt file to read
+        file: PathBuf,
+    },
+    /// Import a Lidl receipt saved as HTML into Grocy, without logging in to Lidl
+    ///
+    /// The date and store of the receipt are asked for if not given.
+    ImportHtml {
+        /// Path of the HTML receipt to read
+        file: PathBuf,
+        /// Id of the receipt (defaults to the name of the file)
+        #[arg(long)]
+        id: Option<String>,
+        /// Date of the purchase (YYYY-MM-DD)
+        #[arg(long)]
+        date: Option<NaiveDate>,
+        /// Id of the Lidl store where the purchase was made
+        #[arg(long)]
+        store_id: Option<String>,
+        /// Name of the Lidl store where the purchase was made
+        #[arg(long)]
+        store_name: Option<String>,
+    },
+    /// Select a receipt from the archive of Lidl responses and import it into Grocy,
+    /// without logging in to Lidl
+    ///
+    /// The receipt is parsed again from the archived responses, so this also works for
+    /// receipts that were deleted from the Lidl Plus app.
+    Replay,
+    /// Undo an import into Grocy, undoing its purchases, removing the barcodes it created
+    /// and restoring the last prices of barcodes it updated
+    UndoImport {
+        /// Id of the imported receipt (defaults to selecting from the recorded imports)
+        receipt_id: Option<String>,
+    },
+    /// List, edit or remove the mappings of stores to shopping locations of Grocy
+    Stores {
+        #[command(subcommand)]
+        action: StoresAction,
+    },
+    /// Remove personal data from a Lidl receipt saved as HTML or JSON, so that it can be
+    /// shared (e.g., in a bug report)
+    ///
+    /// The store address, payment details, return code and promotion ids are censored,
+    /// while the rest of the receipt is kept as-is.
+    Anonymize {
+        /// Path of the HTML or JSON receipt to read
+        file: PathBuf,
+        /// Path of the anonymized receipt to write (defaults to printing it)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Select a receipt from a CSV file and import it into Grocy, for purchases outside Lidl
+    ///
+    /// The file has a header line and these columns: date, store, item, barcode, quantity,
+    /// unit_price, discount and, optionally, unit (pcs, kg, g, l or lb). Lines with the same
+    /// date and store make up a receipt.
+    Csv {
+        /// Path of the CSV file to read
+        file: PathBuf,
+        /// ISO 4217 code of the currency of the prices in the file
+        #[arg(long, default_value = "EUR")]
+        currency: String,
+        /// Symbol of the currency, shown next to amounts; known for common currencies
+        #[arg(long)]
+        currency_symbol: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum StoresAction {
+    /// List the stores mapped to shopping locations of Grocy
+    List,
+    /// Map a store to another shopping location of Grocy, or to a new one
+    Edit {
+        /// Id of the store (e.g., the Lidl store id)
+        store_id: String,
+    },
+    /// Remove the mapping of a store, so that it is asked for again on its next import
+    Remove {
+        /// Id of the store (e.g., the Lidl store id)
+        store_id: String,
+    },
 }
 
 fn main() -> Result<()> {
+    let cli = Cli::parse();
     let mut cfg: Config = confy::load(CONFIG_NAME, Some(CONFIG_NAME))?;
 
-    let receipt = fetch_receipt_from_lidl(&mut cfg.lidl)?;
-    purchase_lidl_products(&mut cfg.grocy, receipt)?;
-
+    // save the configuration even if the command fails, so that credentials and
+    // store mappings entered so far are not lost
+    let result = run(cli, &mut cfg);
     confy::store(CONFIG_NAME, Some(CONFIG_NAME), cfg)?;
 
+    result
+}
+
+fn run(cli: Cli, cfg: &mut Config) -> Result<()> {
+    let filter = cli.filter.into();
+    let archive = cli.archive.or_else(|| cfg.lidl.archive.clone());
+    match cli.command {
+        None => {
+            // the lines are completed with the HTML receipt (e.g., with its discounts and
+            // units) before being imported
+            let receipt = fetch_receipt_from_lidl(&mut cfg.lidl, filter, archive, true)?;
+            purchase_receipt_products(&mut cfg.grocy, receipt)?;
+        }
+        Some(Command::Export { file }) => {
+            let receipt = fetch_receipt_from_lidl(&mut cfg.lidl, filter, archive, false)?;
+            ir::file::write_receipt(File::create(&file)?, &receipt)?;
+            println!("Receipt saved to {}", file.display());
+        }
+        Some(Command::Import { file }) => {
+            let receipt = ir::file::read_receipt(BufReader::new(File::open(file)?))?;
+            purchase_receipt_products(&mut cfg.grocy, receipt)?;
+        }
+        Some(Command::ImportHtml {
+            file,
+            id,
+            date,
+            store_id,
+            store_name,
+        }) => {
+            let receipt = read_html_receipt(&file, id, date, store_id, store_name)?;
+            purchase_receipt_products(&mut cfg.grocy, receipt)?;
+        }
+        Some(Command::Replay) => {
+            let archive = ResponseArchive::new(archive.ok_or(Error::NoArchiveDir)?);
+            let receipt = prompt_receipt(&archive, filter)?;
+            let receipt = archive.get_specific_receipt(&receipt)?;
+            purchase_receipt_products(&mut cfg.grocy, receipt)?;
+        }
+        Some(Command::UndoImport { receipt_id }) => {
+            undo_import(&mut cfg.grocy, receipt_id)?;
+        }
+        Some(Command::Stores { action }) => match action {
+            StoresAction::List => list_store_mappings(&mut cfg.grocy)?,
+            StoresAction::Edit { store_id } => edit_store_mapping(&mut cfg.grocy, store_id)?,
+            StoresAction::Remove { store_id } => remove_store_mapping(&mut cfg.grocy, store_id)?,
+        },
+        Some(Command::Anonymize { file, output }) => {
+            anonymize_receipt_file(&file, output.as_deref())?;
+        }
+        Some(Command::Csv {
+            file,
+            currency,
+            currency_symbol,
+        }) => {
+            let symbol = currency_symbol
+                .or_else(|| csv_store::currency_symbol(&currency).map(str::to_owned))
+                .ok_or_else(|| Error::UnknownCurrencySymbol(currency.clone()))?;
+            let csv_store = CsvStore::open(
+                file,
+                Currency {
+                    id: currency,
+                    symbol,
+                },
+            )?;
+            let receipt = prompt_receipt(&csv_store, filter)?;
+            let receipt = csv_store.get_specific_receipt(&receipt)?;
+            purchase_receipt_products(&mut cfg.grocy, receipt)?;
+        }
+    }
+
     Ok(())
 }
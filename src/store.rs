@@ -0,0 +1,67 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+use inquire::Select;
+use ir::{ReceiptFilter, ReceiptSummary, StoreApi};
+
+use crate::{
+    error::Error,
+    imports::{ImportLog, ImportStatus},
+};
+
+/// Number of receipts shown at once when selecting a receipt, before having to load more
+const RECEIPTS_PER_PAGE: usize = 10;
+
+enum ReceiptChoice {
+    /// A receipt, and whether it was imported before
+    Receipt(ReceiptSummary, Option<ImportStatus>),
+    LoadMore,
+}
+
+impl Display for ReceiptChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Receipt(receipt, None) => receipt.fmt(f),
+            Self::Receipt(receipt, Some(status)) => write!(f, "{} [{}]", receipt, status),
+            Self::LoadMore => write!(f, "Load older receipts..."),
+        }
+    }
+}
+
+pub(super) fn prompt_receipt(
+    store_api: &impl StoreApi,
+    filter: ReceiptFilter,
+) -> Result<ReceiptSummary> {
+    let import_log = ImportLog::load()?;
+    let mut receipts = vec![];
+    let mut receipts_iter = store_api.list_receipts(filter).peekable();
+
+    loop {
+        let first_new_receipt = receipts.len();
+        for receipt in receipts_iter.by_ref().take(RECEIPTS_PER_PAGE) {
+            receipts.push(receipt?);
+        }
+        if receipts.is_empty() {
+            return Err(Error::NoReceiptsFound.into());
+        }
+
+        let mut options: Vec<_> = receipts
+            .iter()
+            .map(|receipt| {
+                let status = import_log.status(&receipt.id);
+                ReceiptChoice::Receipt(receipt.clone(), status)
+            })
+            .collect();
+        if receipts_iter.peek().is_some() {
+            options.push(ReceiptChoice::LoadMore);
+        }
+
+        let choice = Select::new("Select receipt to import:", options)
+            .with_starting_cursor(first_new_receipt.min(receipts.len() - 1))
+            .prompt()?;
+        match choice {
+            ReceiptChoice::Receipt(receipt, _) => return Ok(receipt),
+            ReceiptChoice::LoadMore => continue,
+        }
+    }
+}